@@ -1,3 +1,4 @@
+use layout21::raw::Dir;
 use layout21::raw::Layer;
 use layout21::raw::LayerPurpose;
 use layout21::raw::Layers;
@@ -6,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::{Error, PdkResult};
+
 pub use layout21::raw::Int;
 
 /// The type to use for nonnegative values.
@@ -14,9 +17,30 @@ pub type Uint = isize;
 
 pub use layout21::raw::Units;
 
+/// The layers making up a contact.
+///
+/// The second entry of `layers` is the cut layer. Every other layer is drawn
+/// as a single rectangle enclosing the cuts, with the first entry on top.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub struct ContactStack {
     pub layers: Vec<String>,
+    /// Implant and well layers drawn around the stack's layers.
+    #[serde(default)]
+    pub implants: Vec<StackImplant>,
+}
+
+/// A layer drawn around one of the layers of a [`ContactStack`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct StackImplant {
+    /// The layer to draw.
+    pub layer: String,
+    /// The stack layer that `layer` encloses.
+    pub around: String,
+    /// The enclosure of `around` by `layer`.
+    ///
+    /// Defaults to the enclosure rule of `around` on `layer`.
+    #[serde(default)]
+    pub enclosure: Option<Int>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -26,6 +50,9 @@ pub struct TechConfig {
     pub gamma: f64,
     pub beta: f64,
     pub units: Units,
+    /// If set, transistor gates may only be drawn in this direction.
+    #[serde(default)]
+    pub gate_dir: Option<Dir>,
     layers: HashMap<String, LayerConfig>,
     spacing: Vec<SpacingConfig>,
     stacks: HashMap<String, ContactStack>,
@@ -51,6 +78,14 @@ pub struct Extension {
     pub extend: Int,
 }
 
+/// A cut spacing rule that only applies to large arrays of cuts.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct ArraySpacing {
+    /// The rule applies to arrays with at least this many cuts in both directions.
+    pub min_cuts: Uint,
+    pub space: Int,
+}
+
 #[derive(Debug, Default, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub struct LayerConfig {
     #[serde(default)]
@@ -59,6 +94,9 @@ pub struct LayerConfig {
     pub width: Int,
     #[serde(default)]
     pub space: Int,
+    /// Larger spacing rules for big arrays of cuts on this layer.
+    #[serde(default)]
+    pub array_spacing: Vec<ArraySpacing>,
     #[serde(default)]
     pub area: Int,
     #[serde(default)]
@@ -89,7 +127,24 @@ impl TechConfig {
     }
 
     pub fn layer(&self, l: &str) -> &LayerConfig {
-        self.layers.get(l).unwrap()
+        self.try_layer(l).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`TechConfig::layer`], but returns an error if the layer is not defined.
+    pub fn try_layer(&self, l: &str) -> PdkResult<&LayerConfig> {
+        self.layers
+            .get(l)
+            .ok_or_else(|| Error::UnknownLayer(l.to_string()))
+    }
+
+    /// The names of all layers defined in this config.
+    pub fn layer_names(&self) -> impl Iterator<Item = &str> {
+        self.layers.keys().map(String::as_str)
+    }
+
+    /// All inter-layer spacing rules.
+    pub fn spacing_rules(&self) -> &[SpacingConfig] {
+        &self.spacing
     }
 
     pub fn space(&self, from: &str, to: &str) -> Int {
@@ -107,9 +162,25 @@ impl TechConfig {
     }
 
     pub fn stack(&self, stack: &str) -> &ContactStack {
+        self.try_stack(stack)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`TechConfig::stack`], but returns an error if the stack is not defined.
+    pub fn try_stack(&self, stack: &str) -> PdkResult<&ContactStack> {
         self.stacks
             .get(stack)
-            .unwrap_or_else(|| panic!("no such stack: {}", stack))
+            .ok_or_else(|| Error::UnknownStack(stack.to_string()))
+    }
+
+    /// All contact stacks defined in this config.
+    pub fn stacks(&self) -> impl Iterator<Item = &ContactStack> {
+        self.stacks.values()
+    }
+
+    /// Adds a contact stack named `name`, replacing any existing stack with that name.
+    pub fn add_stack(&mut self, name: impl Into<String>, stack: ContactStack) {
+        self.stacks.insert(name.into(), stack);
     }
 
     pub fn get_layers(&self) -> LayoutResult<Layers> {
@@ -126,6 +197,15 @@ impl TechConfig {
 }
 
 impl LayerConfig {
+    /// The spacing between cuts in a `rows` by `cols` array on this layer.
+    pub fn cut_space(&self, rows: Uint, cols: Uint) -> Int {
+        self.array_spacing
+            .iter()
+            .filter(|rule| rows >= rule.min_cuts && cols >= rule.min_cuts)
+            .map(|rule| rule.space)
+            .fold(self.space, std::cmp::max)
+    }
+
     pub fn extension(&self, l: &str) -> Int {
         self.extensions
             .iter()
@@ -184,6 +264,24 @@ mod tests {
         assert_eq!(tc.layer("licon").enclosure("poly"), 50);
         assert_eq!(tc.layer("licon").one_side_enclosure("poly"), 80);
 
+        let pdiffc = tc.stack("pdiffc");
+        assert_eq!(pdiffc.implants.len(), 2);
+        assert!(pdiffc
+            .implants
+            .iter()
+            .all(|i| i.around == "diff" && i.enclosure.is_none()));
+        assert!(tc.stack("viali").implants.is_empty());
+
+        // A single minimum-width cut must satisfy the cut layer's area rule.
+        for cut in ["licon", "mcon", "via", "via2", "via3", "via4"] {
+            let cut = tc.layer(cut);
+            assert!(cut.width * cut.width >= cut.area);
+        }
+
+        let via = tc.layer("via");
+        assert_eq!(via.cut_space(3, 8), via.space);
+        assert!(via.cut_space(4, 4) > via.space);
+
         Ok(())
     }
 
@@ -193,6 +291,7 @@ mod tests {
             desc: "test layer".into(),
             width: 200,
             space: 300,
+            array_spacing: vec![],
             area: 0,
             layernum: 67,
             purposes: vec![(LayerPurpose::Drawing, 20), (LayerPurpose::Label, 44)],
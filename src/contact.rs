@@ -2,13 +2,19 @@ use std::collections::HashMap;
 use std::fmt::Display;
 
 use layout21::raw::geom::Dir;
-use layout21::raw::{Cell, LayerKey, Rect};
+use layout21::raw::{
+    Abstract, AbstractPort, BoundBoxTrait, Cell, Element, Instance, LayerKey, LayerPurpose, Layout,
+    Point, Rect, Shape,
+};
 use layout21::utils::Ptr;
 use serde::{Deserialize, Serialize};
 
-use crate::config::Int;
-use crate::Ref;
+use crate::config::{Int, TechConfig};
+use crate::geometry::{
+    expand_box, expand_box_min_width, grow_to_area, rect_from_bbox, round_down, translate,
+};
 use crate::{config::Uint, Pdk};
+use crate::{Error, PdkResult, Ref};
 
 pub const MAX_CONTACT_UNITS: isize = 1023;
 
@@ -24,6 +30,36 @@ pub struct ContactParams {
     /// If the contact generator needs more space, it will try to expand in
     /// this direction first.
     pub dir: Dir,
+    /// Per-layer overrides of how the cuts are enclosed.
+    #[builder(default)]
+    pub enclosures: Vec<EnclosureOverride>,
+}
+
+/// A side of a rectangle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Overrides how a contact encloses its cuts on one layer of its stack.
+///
+/// The result must still satisfy the layer's `enclosure` and
+/// `one_side_enclosure` rules; otherwise drawing the contact fails with
+/// [`Error::InvalidEnclosure`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct EnclosureOverride {
+    pub layer: String,
+    /// The sides that get the one-side enclosure.
+    ///
+    /// If [`None`], both sides along the contact's relaxed direction do.
+    pub one_side: Option<Vec<Side>>,
+    /// The minimum width and height of the contact's rect on `layer`.
+    ///
+    /// The rect grows equally on both sides to meet this size.
+    pub min_size: Option<(Int, Int)>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, derive_builder::Builder)]
@@ -32,6 +68,144 @@ pub struct Contact {
     pub rows: Uint,
     pub cols: Uint,
     pub bboxes: HashMap<LayerKey, Rect>,
+    /// The first layer of the contact's stack.
+    pub top: LayerKey,
+    /// The cut layer of the contact's stack.
+    pub cut: LayerKey,
+    /// The last layer of the contact's stack.
+    pub bot: LayerKey,
+    /// The manufacturing grid the contact is placed on.
+    pub grid: Int,
+}
+
+/// How a placed contact lines up with a reference rectangle along one axis.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Align {
+    /// The low edges line up.
+    Start,
+    /// The centers line up, rounded down to the grid.
+    Center,
+    /// The high edges line up.
+    End,
+}
+
+/// How a placed contact lines up with a reference rectangle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Alignment {
+    pub horiz: Align,
+    pub vert: Align,
+}
+
+/// An instance of a [`Contact`], along with its rectangles in the parent's coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedContact {
+    pub inst: Instance,
+    pub bboxes: HashMap<LayerKey, Rect>,
+    pub top: LayerKey,
+    pub cut: LayerKey,
+    pub bot: LayerKey,
+}
+
+impl Contact {
+    /// The contact's rectangle on the top layer of its stack.
+    pub fn top_rect(&self) -> Rect {
+        self.bboxes[&self.top]
+    }
+
+    /// The contact's rectangle on the bottom layer of its stack.
+    pub fn bot_rect(&self) -> Rect {
+        self.bboxes[&self.bot]
+    }
+
+    /// The bounding box of the contact's cuts.
+    pub fn cut_rect(&self) -> Rect {
+        self.bboxes[&self.cut]
+    }
+
+    /// The number of cuts in the contact.
+    pub fn cut_count(&self) -> Uint {
+        self.rows * self.cols
+    }
+
+    /// Places the contact so that its rectangle on `layer` is centered at `point`.
+    pub fn place_centered(&self, point: Point, layer: LayerKey) -> PlacedContact {
+        self.place_aligned(Rect::new(point, point), layer, Alignment::CENTER)
+    }
+
+    /// Places the contact so that its rectangle on `layer` lines up with `rect`.
+    ///
+    /// `rect` may be empty; for instance, aligning the end of a contact to a
+    /// zero-width rect at `x` places the contact just to the left of `x`.
+    pub fn place_aligned(
+        &self,
+        rect: Rect,
+        layer: LayerKey,
+        alignment: Alignment,
+    ) -> PlacedContact {
+        let bbox = self.bboxes[&layer];
+        let loc = Point::new(
+            alignment
+                .horiz
+                .offset(rect.p0.x, rect.p1.x, bbox.p0.x, bbox.p1.x, self.grid),
+            alignment
+                .vert
+                .offset(rect.p0.y, rect.p1.y, bbox.p0.y, bbox.p1.y, self.grid),
+        );
+        let inst = Instance {
+            inst_name: format!("{}_{}_{}", self.cell.read().unwrap().name, loc.x, loc.y),
+            cell: Ptr::clone(&self.cell),
+            loc,
+            reflect_vert: false,
+            angle: None,
+        };
+        PlacedContact {
+            inst,
+            bboxes: self
+                .bboxes
+                .iter()
+                .map(|(layer, bbox)| (*layer, translate(bbox, &loc)))
+                .collect(),
+            top: self.top,
+            cut: self.cut,
+            bot: self.bot,
+        }
+    }
+}
+
+impl Align {
+    /// The offset that lines up the span `lo..hi` with the span `start..end`.
+    fn offset(&self, start: Int, end: Int, lo: Int, hi: Int, grid: Int) -> Int {
+        match self {
+            Align::Start => start - lo,
+            Align::Center => round_down((start + end - lo - hi).div_euclid(2), grid),
+            Align::End => end - hi,
+        }
+    }
+}
+
+impl Alignment {
+    pub const CENTER: Alignment = Alignment::new(Align::Center, Align::Center);
+
+    pub const fn new(horiz: Align, vert: Align) -> Self {
+        Self { horiz, vert }
+    }
+}
+
+impl PlacedContact {
+    /// The placed contact's rectangle on the top layer of its stack.
+    pub fn top_rect(&self) -> Rect {
+        self.bboxes[&self.top]
+    }
+
+    /// The placed contact's rectangle on the bottom layer of its stack.
+    pub fn bot_rect(&self) -> Rect {
+        self.bboxes[&self.bot]
+    }
+
+    /// The bounding box of the placed contact's cuts.
+    pub fn cut_rect(&self) -> Rect {
+        self.bboxes[&self.cut]
+    }
 }
 
 impl Display for ContactParams {
@@ -43,7 +217,39 @@ impl Display for ContactParams {
             self.rows,
             self.cols,
             self.dir.short_form()
-        )
+        )?;
+        for enc in self.enclosures.iter() {
+            write!(f, "_{}", enc)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Side::Left => "l",
+            Side::Right => "r",
+            Side::Top => "t",
+            Side::Bottom => "b",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for EnclosureOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", &self.layer)?;
+        if let Some(ref sides) = self.one_side {
+            write!(f, "_")?;
+            for side in sides.iter() {
+                write!(f, "{}", side)?;
+            }
+        }
+        if let Some((w, h)) = self.min_size {
+            write!(f, "_{}x{}", w, h)?;
+        }
+        Ok(())
     }
 }
 
@@ -55,13 +261,19 @@ impl ContactParams {
 
 impl Pdk {
     pub fn get_contact(&self, params: &ContactParams) -> Ref<Contact> {
+        self.try_get_contact(params)
+            .unwrap_or_else(|err| panic!("failed to draw contact {}: {}", params, err))
+    }
+
+    /// Like [`Pdk::get_contact`], but returns an error if the contact cannot be drawn.
+    pub fn try_get_contact(&self, params: &ContactParams) -> PdkResult<Ref<Contact>> {
         let mut map = self.contacts.write().unwrap();
         if let Some(c) = map.get(params) {
-            c.clone()
+            Ok(c.clone())
         } else {
-            let c = self.draw_contact(params);
+            let c = self.draw_contact(params)?;
             map.insert(params.to_owned(), c.clone());
-            c
+            Ok(c)
         }
     }
 
@@ -72,37 +284,45 @@ impl Pdk {
         layer: LayerKey,
         width: Int,
     ) -> Option<Ref<Contact>> {
-        let mut low = 1;
-        let mut high = MAX_CONTACT_UNITS;
-        let mut result = None;
-
-        let stack = stack.into();
+        self.try_get_contact_sized(stack, dir, layer, width)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
 
-        while high > low {
-            let mid = (high + low) / 2;
-            let (rows, cols) = match dir {
-                Dir::Horiz => (1, mid),
-                Dir::Vert => (mid, 1),
-            };
-            let params = ContactParams::builder()
-                .rows(rows)
-                .cols(cols)
-                .stack(stack.clone())
-                .dir(dir)
-                .build()
-                .unwrap();
-            let ct = self.get_contact(&params);
-            let bbox = ct.bboxes.get(&layer).unwrap();
-
-            if bbox.span(dir).length() <= width {
-                result = Some(ct);
-                low = mid + 1;
-            } else {
-                high = mid;
-            }
-        }
+    /// Like [`Pdk::get_contact_sized`], but returns an error if the stack or layer is invalid.
+    pub fn try_get_contact_sized(
+        &self,
+        stack: impl Into<String>,
+        dir: Dir,
+        layer: LayerKey,
+        width: Int,
+    ) -> PdkResult<Option<Ref<Contact>>> {
+        self.contact_sized(&stack.into(), dir, dir, layer, width)
+    }
 
-        result
+    /// Gets the largest single-row contact along `axis`, relaxed in direction `dir`,
+    /// whose boundary on `layer` is at most `width` long.
+    pub(crate) fn contact_sized(
+        &self,
+        stack: &str,
+        axis: Dir,
+        dir: Dir,
+        layer: LayerKey,
+        width: Int,
+    ) -> PdkResult<Option<Ref<Contact>>> {
+        let units = self.max_contact_units(stack, layer, dir, axis, 1, width)?;
+        let (rows, cols) = match (units, axis) {
+            (None, _) => return Ok(None),
+            (Some(n), Dir::Horiz) => (1, n),
+            (Some(n), Dir::Vert) => (n, 1),
+        };
+        let params = ContactParams::builder()
+            .rows(rows)
+            .cols(cols)
+            .stack(stack)
+            .dir(dir)
+            .build()
+            .unwrap();
+        Ok(Some(self.try_get_contact(&params)?))
     }
 
     /// Gets the largest contact whose boundary on `layer` fits within the provided [`Rect`]'s
@@ -115,11 +335,17 @@ impl Pdk {
         layer: LayerKey,
         bbox: impl Into<Rect>,
     ) -> Option<Ref<Contact>> {
-        let mut low_r = 1;
-        let mut high_r = MAX_CONTACT_UNITS;
-        let mut low_c = 1;
-        let mut high_c = MAX_CONTACT_UNITS;
+        self.try_get_contact_within(stack, layer, bbox)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
 
+    /// Like [`Pdk::get_contact_within`], but returns an error if the stack or layer is invalid.
+    pub fn try_get_contact_within(
+        &self,
+        stack: impl Into<String>,
+        layer: LayerKey,
+        bbox: impl Into<Rect>,
+    ) -> PdkResult<Option<Ref<Contact>>> {
         let stack = stack.into();
         let bbox = bbox.into();
         let dir = if bbox.width() > bbox.height() {
@@ -128,56 +354,374 @@ impl Pdk {
             Dir::Vert
         };
 
-        let mut result;
+        // Landing pads only grow along `dir` to meet the minimum area rule,
+        // so the number of cuts across `dir` only depends on the number along it
+        // through the cut spacing of large arrays.
+        let (across_limit, along_limit) = match dir {
+            Dir::Horiz => (bbox.height(), bbox.width()),
+            Dir::Vert => (bbox.width(), bbox.height()),
+        };
+        let other = match dir {
+            Dir::Horiz => Dir::Vert,
+            Dir::Vert => Dir::Horiz,
+        };
+        let mut across = match self.max_contact_units(&stack, layer, dir, other, 1, across_limit)? {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+        // Each pass either confirms that `across` still fits or strictly reduces it.
+        let along = loop {
+            let along =
+                match self.max_contact_units(&stack, layer, dir, dir, across, along_limit)? {
+                    Some(n) => n,
+                    None => return Ok(None),
+                };
+            match self.max_contact_units(&stack, layer, dir, other, along, across_limit)? {
+                Some(n) if n >= across => break along,
+                Some(n) => across = n,
+                None => return Ok(None),
+            }
+        };
+        let (rows, cols) = match dir {
+            Dir::Horiz => (across, along),
+            Dir::Vert => (along, across),
+        };
+        let params = ContactParams::builder()
+            .rows(rows)
+            .cols(cols)
+            .stack(stack)
+            .dir(dir)
+            .build()
+            .unwrap();
+        Ok(Some(self.try_get_contact(&params)?))
+    }
+
+    /// The largest number of cuts that can be placed along `axis` in a contact
+    /// on `stack` relaxed in direction `dir`, with `other` cuts across `axis`,
+    /// such that the contact's extent on `layer` along `axis` is at most `limit`.
+    ///
+    /// Computed from the stack's rules without drawing any contacts.
+    pub(crate) fn max_contact_units(
+        &self,
+        stack: &str,
+        layer: LayerKey,
+        dir: Dir,
+        axis: Dir,
+        other: Uint,
+        limit: Int,
+    ) -> PdkResult<Option<Uint>> {
+        let pads = self.metal_names();
+        let tc = self.config.read().unwrap();
+        let stack_cfg = tc.try_stack(stack)?;
+        let layer_name = self.layer_name(layer);
+        let ctlay = tc.try_layer(
+            stack_cfg
+                .layers
+                .get(1)
+                .ok_or_else(|| Error::InvalidStack(stack.to_string()))?,
+        )?;
+        let pitch = ctlay.width + ctlay.space;
+
+        let extent = |n: Uint| -> PdkResult<Int> {
+            let (rows, cols) = match axis {
+                Dir::Horiz => (other, n),
+                Dir::Vert => (n, other),
+            };
+            stack_boxes(&tc, stack, rows, cols, dir, &[], &pads)?
+                .into_iter()
+                .find(|(name, _)| *name == layer_name)
+                .map(|(_, bbox)| bbox.span(axis).length())
+                .ok_or_else(|| Error::UnknownLayer(layer_name.clone()))
+        };
+
+        // The extent grows by exactly one pitch per cut once the layer's
+        // minimum width no longer applies, so start from that estimate
+        // and correct for the first few cuts.
+        let mut n = ((limit - extent(1)?) / pitch + 1).clamp(0, MAX_CONTACT_UNITS);
+        while n > 0 && extent(n)? > limit {
+            n -= 1;
+        }
+        while n < MAX_CONTACT_UNITS && extent(n + 1)? <= limit {
+            n += 1;
+        }
+
+        Ok(if n > 0 { Some(n) } else { None })
+    }
+
+    /// Draws a contact on the stack named by `params`,
+    /// including any implants the stack declares.
+    pub(crate) fn draw_stack_contact(&self, params: &ContactParams) -> PdkResult<Ref<Contact>> {
+        let rows = params.rows;
+        let cols = params.cols;
+
+        if rows <= 0 || cols <= 0 {
+            return Err(Error::InvalidContactSize { rows, cols });
+        }
+        let tc = self.config.read().unwrap();
+        let layers = self.layers.read().unwrap();
+        let keyname = |name: &str| {
+            layers
+                .keyname(name)
+                .ok_or_else(|| Error::UnknownLayer(name.to_string()))
+        };
+        let pads = self.metal_names();
+        let boxes = stack_boxes(
+            &tc,
+            &params.stack,
+            rows,
+            cols,
+            params.dir,
+            &params.enclosures,
+            &pads,
+        )?;
+        let stack = tc.try_stack(&params.stack)?;
+
+        let ctlay_name = &stack.layers[1];
+        let ctlay = keyname(ctlay_name)?;
+
+        let mut elems = Vec::new();
+
+        let ctw = tc.try_layer(ctlay_name)?.width;
+        let cts = tc.try_layer(ctlay_name)?.cut_space(rows, cols);
 
-        loop {
-            if high_r < low_r || high_c < low_c {
-                return None;
+        for i in 0..rows {
+            for j in 0..cols {
+                let left = j * (ctw + cts);
+                let bot = i * (ctw + cts);
+                let ct_box = Rect {
+                    p0: Point::new(left, bot),
+                    p1: Point::new(left + ctw, bot + ctw),
+                };
+
+                elems.push(Element {
+                    net: None,
+                    layer: ctlay,
+                    purpose: LayerPurpose::Drawing,
+                    inner: Shape::Rect(ct_box),
+                });
+            }
+        }
+
+        let mut bbox_map = HashMap::with_capacity(boxes.len());
+        let mut outline = boxes[0].1;
+        let mut ports = Vec::with_capacity(stack.layers.len() - 1);
+
+        for (i, (lay_name, laybox)) in boxes.into_iter().enumerate() {
+            let lay = keyname(&lay_name)?;
+            bbox_map.insert(lay, laybox);
+            // The cuts were drawn above.
+            if lay_name == *ctlay_name {
+                continue;
             }
 
-            assert!(high_r >= low_r);
-            assert!(high_c >= low_c);
-
-            let r = (low_r + high_r + 1) / 2;
-            let c = (low_c + high_c + 1) / 2;
-
-            let params = ContactParams::builder()
-                .rows(r)
-                .cols(c)
-                .stack(stack.clone())
-                .dir(dir)
-                .build()
-                .unwrap();
-            let ct = self.get_contact(&params);
-            let outline = ct.bboxes.get(&layer).unwrap();
-
-            match (
-                outline.width() <= bbox.width(),
-                outline.height() <= bbox.height(),
-            ) {
-                (true, true) => {
-                    result = Some(ct);
-                    low_r = r;
-                    low_c = c;
-                    if r == high_r && c == high_c {
-                        break;
-                    }
-                }
-                (true, false) => {
-                    low_c = c;
-                    high_r = r - 1;
-                }
-                (false, true) => {
-                    low_r = r;
-                    high_c = c - 1;
-                }
-                (false, false) => {
-                    high_r = r - 1;
-                    high_c = c - 1;
-                }
+            let shape = Shape::Rect(laybox);
+            // Implants are not connectable, so only stack layers get ports
+            // and make up the outline.
+            if i < stack.layers.len() {
+                outline = rect_from_bbox(&outline.union(&laybox.bbox()));
+                ports.push(AbstractPort {
+                    net: lay_name.clone(),
+                    shapes: HashMap::from([(lay, vec![shape.clone()])]),
+                });
             }
+
+            elems.push(Element {
+                net: None,
+                layer: lay,
+                purpose: LayerPurpose::Drawing,
+                inner: shape,
+            });
         }
 
-        result
+        let name = format!("{}", params);
+
+        let layout = Layout {
+            name: name.clone(),
+            insts: vec![],
+            annotations: vec![],
+            elems,
+        };
+
+        let abs = Abstract {
+            name: name.clone(),
+            outline: Some(Element {
+                net: None,
+                layer: keyname(&stack.layers[0])?,
+                purpose: LayerPurpose::Drawing,
+                inner: Shape::Rect(outline),
+            }),
+            blockages: HashMap::new(),
+            ports,
+        };
+
+        let cell = Cell {
+            name,
+            abs: Some(abs),
+            layout: Some(layout),
+        };
+
+        let cell = Ptr::new(cell);
+
+        Ok(std::sync::Arc::new(Contact {
+            cell,
+            rows: params.rows,
+            cols: params.cols,
+            bboxes: bbox_map,
+            top: keyname(&stack.layers[0])?,
+            cut: ctlay,
+            bot: keyname(stack.layers.last().unwrap())?,
+            grid: tc.grid,
+        }))
+    }
+}
+
+/// The rectangle drawn on every layer of a `rows` by `cols` contact on `stack`.
+///
+/// The cut layer comes first, followed by the stack's other layers
+/// and then its implants, in the order they are declared.
+/// Rectangles on the layers in `pads` grow along `dir` to meet the minimum area rule.
+fn stack_boxes(
+    tc: &TechConfig,
+    stack: &str,
+    rows: Uint,
+    cols: Uint,
+    dir: Dir,
+    enclosures: &[EnclosureOverride],
+    pads: &[&str],
+) -> PdkResult<Vec<(String, Rect)>> {
+    let stack_name = stack;
+    let stack = tc.try_stack(stack)?;
+    if stack.layers.len() < 2 {
+        return Err(Error::InvalidStack(stack_name.to_string()));
+    }
+    let ctlay = &stack.layers[1];
+    let ct_bbox = cut_bbox(tc, ctlay, rows, cols)?;
+
+    if let Some(enc) = enclosures
+        .iter()
+        .find(|enc| enc.layer == *ctlay || !stack.layers.contains(&enc.layer))
+    {
+        return Err(Error::UnknownLayer(enc.layer.clone()));
+    }
+
+    let mut boxes = vec![(ctlay.clone(), ct_bbox)];
+    for lay in stack.layers.iter().filter(|lay| *lay != ctlay) {
+        let mut laybox = match enclosures.iter().find(|enc| enc.layer == *lay) {
+            Some(enc) => override_layer_box(tc, &ct_bbox, ctlay, enc, dir)?,
+            None => stack_layer_box(tc, &ct_bbox, ctlay, lay, dir)?,
+        };
+        if pads.contains(&lay.as_str()) {
+            grow_to_area(&mut laybox, tc.try_layer(lay)?.area, dir, tc.grid);
+        }
+        boxes.push((lay.clone(), laybox));
+    }
+
+    for implant in stack.implants.iter() {
+        let mut implant_box = boxes
+            .iter()
+            .find(|(lay, _)| *lay == implant.around)
+            .map(|(_, laybox)| *laybox)
+            .ok_or_else(|| Error::UnknownLayer(implant.around.clone()))?;
+        let enclosure = match implant.enclosure {
+            Some(enclosure) => enclosure,
+            None => tc.try_layer(&implant.around)?.enclosure(&implant.layer),
+        };
+        expand_box(&mut implant_box, enclosure);
+        boxes.push((implant.layer.clone(), implant_box));
     }
+
+    Ok(boxes)
+}
+
+/// The bounding box of the cuts in a `rows` by `cols` contact on `ctlay`.
+fn cut_bbox(tc: &TechConfig, ctlay: &str, rows: Uint, cols: Uint) -> PdkResult<Rect> {
+    let ctlay = tc.try_layer(ctlay)?;
+    let space = ctlay.cut_space(rows, cols);
+    let ctbw = ctlay.width * cols + space * (cols - 1);
+    let ctbh = ctlay.width * rows + space * (rows - 1);
+    Ok(Rect::new(Point::zero(), Point::new(ctbw, ctbh)))
+}
+
+/// The rectangle drawn on `lay` to enclose the cuts in `ct_bbox`.
+fn stack_layer_box(
+    tc: &TechConfig,
+    ct_bbox: &Rect,
+    ctlay: &str,
+    lay: &str,
+    dir: Dir,
+) -> PdkResult<Rect> {
+    let mut laybox = *ct_bbox;
+    expand_box(&mut laybox, tc.try_layer(ctlay)?.enclosure(lay));
+    expand_box_min_width(&mut laybox, tc.try_layer(lay)?.width, tc.grid);
+    let ose = tc.try_layer(ctlay)?.one_side_enclosure(lay);
+
+    match dir {
+        Dir::Vert => {
+            laybox.p0.y = std::cmp::min(laybox.p0.y, ct_bbox.p0.y - ose);
+            laybox.p1.y = std::cmp::max(laybox.p0.y, ct_bbox.p1.y + ose);
+        }
+        Dir::Horiz => {
+            laybox.p0.x = std::cmp::min(laybox.p0.x, ct_bbox.p0.x - ose);
+            laybox.p1.x = std::cmp::max(laybox.p0.x, ct_bbox.p1.x + ose);
+        }
+    }
+
+    Ok(laybox)
+}
+
+/// The rectangle drawn on `enc.layer` to enclose the cuts in `ct_bbox`,
+/// checked against the layer's enclosure rules.
+fn override_layer_box(
+    tc: &TechConfig,
+    ct_bbox: &Rect,
+    ctlay: &str,
+    enc: &EnclosureOverride,
+    dir: Dir,
+) -> PdkResult<Rect> {
+    let lay = &enc.layer;
+    let ctcfg = tc.try_layer(ctlay)?;
+    let min_enc = ctcfg.enclosure(lay);
+    let ose = ctcfg.one_side_enclosure(lay);
+
+    let mut laybox = stack_layer_box(tc, ct_bbox, ctlay, lay, dir)?;
+    if let Some(ref sides) = enc.one_side {
+        laybox = *ct_bbox;
+        expand_box(&mut laybox, min_enc);
+        expand_box_min_width(&mut laybox, tc.try_layer(lay)?.width, tc.grid);
+        for side in sides.iter() {
+            match side {
+                Side::Left => laybox.p0.x = std::cmp::min(laybox.p0.x, ct_bbox.p0.x - ose),
+                Side::Right => laybox.p1.x = std::cmp::max(laybox.p1.x, ct_bbox.p1.x + ose),
+                Side::Bottom => laybox.p0.y = std::cmp::min(laybox.p0.y, ct_bbox.p0.y - ose),
+                Side::Top => laybox.p1.y = std::cmp::max(laybox.p1.y, ct_bbox.p1.y + ose),
+            }
+        }
+    }
+
+    if let Some((w, h)) = enc.min_size {
+        let grow = |lo: &mut Int, hi: &mut Int, len: Int| {
+            let extra = len - (*hi - *lo);
+            if extra > 0 {
+                let half = round_down(extra / 2, tc.grid);
+                *lo -= half;
+                *hi += extra - half;
+            }
+        };
+        grow(&mut laybox.p0.x, &mut laybox.p1.x, w);
+        grow(&mut laybox.p0.y, &mut laybox.p1.y, h);
+    }
+
+    // Every side needs the minimum enclosure, and at least one pair of
+    // opposite sides needs the one-side enclosure.
+    let left = ct_bbox.p0.x - laybox.p0.x;
+    let right = laybox.p1.x - ct_bbox.p1.x;
+    let bot = ct_bbox.p0.y - laybox.p0.y;
+    let top = laybox.p1.y - ct_bbox.p1.y;
+    let valid = [left, right, bot, top].iter().all(|&e| e >= min_enc)
+        && ((left >= ose && right >= ose) || (bot >= ose && top >= ose));
+    if !valid {
+        return Err(Error::InvalidEnclosure(lay.clone()));
+    }
+
+    Ok(laybox)
 }
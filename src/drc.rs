@@ -0,0 +1,1001 @@
+//! A simple design rule checker driven by the rules in a [`TechConfig`].
+//!
+//! Cells are flattened into rectangles, which are then checked against the
+//! width, space, array spacing, area, enclosure, extension and inter-layer
+//! spacing rules of the technology. The checks are intended for regression testing of
+//! layout generators, and are not a replacement for signoff DRC.
+//!
+//! Shapes on the same layer that overlap or abut are treated as a single
+//! connected region. Spacing rules between two layers only apply to regions
+//! that do not touch; enclosure and extension rules only apply to shapes
+//! that overlap. Shapes on layers of a contact stack must in addition be
+//! covered by the other layers of at least one stack they can belong to,
+//! so that missing enclosures are reported.
+//! Non-rectangular shapes are approximated by their bounding boxes.
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use layout21::raw::{BoundBoxTrait, Cell, Int, LayerKey, Point, Rect, Shape};
+use layout21::utils::Ptr;
+
+use crate::config::{ArraySpacing, TechConfig};
+use crate::Pdk;
+
+/// The name of the derived layer consisting of the overlap of poly and diff.
+pub const GATE_LAYER: &str = "gate";
+
+/// The kind of design rule that was violated.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DrcRule {
+    /// Minimum width of a shape.
+    Width,
+    /// Minimum spacing between unconnected shapes on the same layer.
+    Space,
+    /// Minimum area of a connected region.
+    Area,
+    /// Minimum enclosure of a shape by the given layer.
+    Enclosure(String),
+    /// Minimum extension of a shape past the given layer.
+    Extension(String),
+    /// Minimum spacing between a shape and shapes on the given layer.
+    Spacing(String),
+}
+
+/// A single design rule violation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrcViolation {
+    /// The rule that was violated.
+    pub rule: DrcRule,
+    /// The name of the layer on which the rule was violated.
+    pub layer: String,
+    /// The value required by the rule.
+    pub required: Int,
+    /// The offending rectangles.
+    ///
+    /// For rules involving two layers, rectangles on [`DrcViolation::layer`]
+    /// are listed first.
+    pub rects: Vec<Rect>,
+}
+
+impl Display for DrcRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Width => write!(f, "width"),
+            Self::Space => write!(f, "space"),
+            Self::Area => write!(f, "area"),
+            Self::Enclosure(l) => write!(f, "enclosure by {}", l),
+            Self::Extension(l) => write!(f, "extension past {}", l),
+            Self::Spacing(l) => write!(f, "spacing to {}", l),
+        }
+    }
+}
+
+impl Display for DrcViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} (required {}): {:?}",
+            self.layer, self.rule, self.required, self.rects
+        )
+    }
+}
+
+impl Pdk {
+    /// Checks the given cell against the design rules of this PDK.
+    ///
+    /// Returns a list of all violations found; the list is empty if the cell is clean.
+    pub fn drc(&self, cell: &Ptr<Cell>) -> Vec<DrcViolation> {
+        let shapes = {
+            let cell = cell.read().unwrap();
+            flatten(&cell)
+        };
+
+        let tc = self.config.read().unwrap();
+        let mut rects = HashMap::new();
+        for name in tc.layer_names() {
+            if let Some(key) = self.get_layerkey(name) {
+                let layer_rects = shapes.get(&key).cloned().unwrap_or_default();
+                rects.insert(name.to_string(), layer_rects);
+            }
+        }
+
+        check(&tc, rects)
+    }
+}
+
+/// Checks the given rectangles, grouped by layer name, against the rules in `tc`.
+pub fn check(tc: &TechConfig, mut rects: HashMap<String, Vec<Rect>>) -> Vec<DrcViolation> {
+    let gates = derive_gates(
+        rects.get("poly").map(Vec::as_slice).unwrap_or_default(),
+        rects.get("diff").map(Vec::as_slice).unwrap_or_default(),
+    );
+    rects.insert(GATE_LAYER.to_string(), gates);
+
+    let mut violations = Vec::new();
+    let empty = Vec::new();
+    let required = required_enclosures(tc);
+
+    let mut names = tc.layer_names().collect::<Vec<_>>();
+    names.sort_unstable();
+
+    for name in names {
+        let cfg = tc.layer(name);
+        let layer_rects = rects.get(name).unwrap_or(&empty);
+        if layer_rects.is_empty() {
+            continue;
+        }
+        let regions = connected_regions(layer_rects);
+
+        if cfg.width > 0 {
+            check_width(name, cfg.width, layer_rects, &mut violations);
+        }
+        if cfg.space > 0 {
+            check_space(name, cfg.space, layer_rects, &regions, &mut violations);
+        }
+        for rule in cfg.array_spacing.iter() {
+            check_array_space(name, rule, layer_rects, &regions, &mut violations);
+        }
+        if cfg.area > 0 {
+            check_area(name, cfg.area, layer_rects, &regions, &mut violations);
+        }
+
+        let mut enclosing = cfg
+            .enclosures
+            .iter()
+            .map(|enc| enc.layer.as_str())
+            .collect::<Vec<_>>();
+        enclosing.sort_unstable();
+        enclosing.dedup();
+        for other in enclosing {
+            let other_rects = rects.get(other).unwrap_or(&empty);
+            check_enclosure(
+                name,
+                other,
+                cfg.enclosure(other),
+                cfg.one_side_enclosure(other),
+                layer_rects,
+                other_rects,
+                &mut violations,
+            );
+        }
+
+        if let Some(alternatives) = required.get(name) {
+            check_required_enclosure(name, tc, alternatives, layer_rects, &rects, &mut violations);
+        }
+
+        for ext in cfg.extensions.iter() {
+            let other_rects = rects.get(&ext.layer).unwrap_or(&empty);
+            check_extension(
+                name,
+                &ext.layer,
+                ext.extend,
+                layer_rects,
+                other_rects,
+                &mut violations,
+            );
+        }
+    }
+
+    for rule in tc.spacing_rules() {
+        let from = rects.get(&rule.from).unwrap_or(&empty);
+        let to = rects.get(&rule.to).unwrap_or(&empty);
+        let from_regions = connected_regions(from);
+        let to_regions = connected_regions(to);
+
+        // Regions that interact with each other are exempt from spacing rules.
+        let mut interacting = HashSet::new();
+        for (a, ra) in from.iter().zip(from_regions.iter()) {
+            for (b, rb) in to.iter().zip(to_regions.iter()) {
+                if gap(a, b) == (0, 0) {
+                    interacting.insert((*ra, *rb));
+                }
+            }
+        }
+
+        for (a, ra) in from.iter().zip(from_regions.iter()) {
+            for (b, rb) in to.iter().zip(to_regions.iter()) {
+                if interacting.contains(&(*ra, *rb)) {
+                    continue;
+                }
+                let (dx, dy) = gap(a, b);
+                if dx * dx + dy * dy < rule.dist * rule.dist {
+                    violations.push(DrcViolation {
+                        rule: DrcRule::Spacing(rule.to.clone()),
+                        layer: rule.from.clone(),
+                        required: rule.dist,
+                        rects: vec![*a, *b],
+                    });
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// Flattens the given cell into a collection of rectangles on each layer.
+///
+/// The cell's abstract view is ignored.
+pub fn flatten(cell: &Cell) -> HashMap<LayerKey, Vec<Rect>> {
+    let mut out = HashMap::new();
+    flatten_inner(cell, &Transform::identity(), &mut out);
+    out
+}
+
+fn flatten_inner(cell: &Cell, trans: &Transform, out: &mut HashMap<LayerKey, Vec<Rect>>) {
+    let layout = match cell.layout {
+        Some(ref layout) => layout,
+        None => return,
+    };
+
+    for elem in layout.elems.iter() {
+        let rect = match elem.inner {
+            Shape::Rect(r) => r,
+            ref shape => shape.bbox().into_rect(),
+        };
+        out.entry(elem.layer)
+            .or_default()
+            .push(trans.apply_rect(&rect));
+    }
+
+    for inst in layout.insts.iter() {
+        let inner = Transform::from_inst(inst.loc, inst.reflect_vert, inst.angle);
+        let trans = trans.compose(&inner);
+        let cell = inst.cell.read().unwrap();
+        flatten_inner(&cell, &trans, out);
+    }
+}
+
+/// A Manhattan transformation: an integer 2x2 matrix followed by an offset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Transform {
+    a: [[Int; 2]; 2],
+    offset: Point,
+}
+
+impl Transform {
+    fn identity() -> Self {
+        Self {
+            a: [[1, 0], [0, 1]],
+            offset: Point::zero(),
+        }
+    }
+
+    /// The transformation applied by an instance: reflection about the x-axis,
+    /// then counterclockwise rotation, then translation.
+    fn from_inst(loc: Point, reflect_vert: bool, angle: Option<f64>) -> Self {
+        let angle = angle.unwrap_or_default();
+        let quarters = (angle / 90.).round();
+        assert!(
+            (angle - 90. * quarters).abs() < 1e-6,
+            "only rotations by multiples of 90 degrees are supported"
+        );
+        let rot = match (quarters as i64).rem_euclid(4) {
+            0 => [[1, 0], [0, 1]],
+            1 => [[0, -1], [1, 0]],
+            2 => [[-1, 0], [0, -1]],
+            _ => [[0, 1], [-1, 0]],
+        };
+        let refl = if reflect_vert {
+            [[1, 0], [0, -1]]
+        } else {
+            [[1, 0], [0, 1]]
+        };
+        Self {
+            a: matmul(&rot, &refl),
+            offset: loc,
+        }
+    }
+
+    /// The transformation that applies `inner`, then `self`.
+    fn compose(&self, inner: &Self) -> Self {
+        let ofs = self.apply(&inner.offset);
+        Self {
+            a: matmul(&self.a, &inner.a),
+            offset: ofs,
+        }
+    }
+
+    fn apply(&self, p: &Point) -> Point {
+        Point::new(
+            self.a[0][0] * p.x + self.a[0][1] * p.y + self.offset.x,
+            self.a[1][0] * p.x + self.a[1][1] * p.y + self.offset.y,
+        )
+    }
+
+    fn apply_rect(&self, r: &Rect) -> Rect {
+        let p0 = self.apply(&r.p0);
+        let p1 = self.apply(&r.p1);
+        Rect::new(
+            Point::new(p0.x.min(p1.x), p0.y.min(p1.y)),
+            Point::new(p0.x.max(p1.x), p0.y.max(p1.y)),
+        )
+    }
+}
+
+fn matmul(x: &[[Int; 2]; 2], y: &[[Int; 2]; 2]) -> [[Int; 2]; 2] {
+    let mut out = [[0; 2]; 2];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = x[i][0] * y[0][j] + x[i][1] * y[1][j];
+        }
+    }
+    out
+}
+
+fn check_width(name: &str, width: Int, rects: &[Rect], violations: &mut Vec<DrcViolation>) {
+    for (i, r) in rects.iter().enumerate() {
+        if std::cmp::min(r.width(), r.height()) >= width {
+            continue;
+        }
+        // Narrow rectangles that are entirely covered by other shapes are harmless.
+        let others = rects
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, r)| *r)
+            .collect::<Vec<_>>();
+        if !covers(&others, r, None) {
+            violations.push(DrcViolation {
+                rule: DrcRule::Width,
+                layer: name.to_string(),
+                required: width,
+                rects: vec![*r],
+            });
+        }
+    }
+}
+
+fn check_space(
+    name: &str,
+    space: Int,
+    rects: &[Rect],
+    regions: &[usize],
+    violations: &mut Vec<DrcViolation>,
+) {
+    for i in 0..rects.len() {
+        for j in (i + 1)..rects.len() {
+            if regions[i] == regions[j] {
+                continue;
+            }
+            let (dx, dy) = gap(&rects[i], &rects[j]);
+            if dx * dx + dy * dy < space * space {
+                violations.push(DrcViolation {
+                    rule: DrcRule::Space,
+                    layer: name.to_string(),
+                    required: space,
+                    rects: vec![rects[i], rects[j]],
+                });
+            }
+        }
+    }
+}
+
+/// Checks the spacing between cuts in arrays large enough for `rule` to apply.
+///
+/// Cuts closer than the rule's spacing are considered part of the same array.
+fn check_array_space(
+    name: &str,
+    rule: &ArraySpacing,
+    rects: &[Rect],
+    regions: &[usize],
+    violations: &mut Vec<DrcViolation>,
+) {
+    let too_close = |a: &Rect, b: &Rect| {
+        let (dx, dy) = gap(a, b);
+        dx * dx + dy * dy < rule.space * rule.space
+    };
+    let arrays = group(rects, too_close);
+
+    let mut large = HashSet::new();
+    for array in arrays.iter().collect::<HashSet<_>>() {
+        let members = rects
+            .iter()
+            .zip(arrays.iter())
+            .filter(|(_, a)| *a == array)
+            .map(|(r, _)| r);
+        let xs = members.clone().map(|r| r.p0.x).collect::<HashSet<_>>();
+        let ys = members.map(|r| r.p0.y).collect::<HashSet<_>>();
+        if xs.len() as Int >= rule.min_cuts && ys.len() as Int >= rule.min_cuts {
+            large.insert(*array);
+        }
+    }
+
+    for i in 0..rects.len() {
+        for j in (i + 1)..rects.len() {
+            if regions[i] == regions[j] || arrays[i] != arrays[j] || !large.contains(&arrays[i]) {
+                continue;
+            }
+            if too_close(&rects[i], &rects[j]) {
+                violations.push(DrcViolation {
+                    rule: DrcRule::Space,
+                    layer: name.to_string(),
+                    required: rule.space,
+                    rects: vec![rects[i], rects[j]],
+                });
+            }
+        }
+    }
+}
+
+fn check_area(
+    name: &str,
+    area: Int,
+    rects: &[Rect],
+    regions: &[usize],
+    violations: &mut Vec<DrcViolation>,
+) {
+    let mut ids = regions.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+
+    for id in ids {
+        let members = rects
+            .iter()
+            .zip(regions.iter())
+            .filter(|(_, r)| **r == id)
+            .map(|(rect, _)| *rect)
+            .collect::<Vec<_>>();
+        if union_area(&members) < area {
+            violations.push(DrcViolation {
+                rule: DrcRule::Area,
+                layer: name.to_string(),
+                required: area,
+                rects: members,
+            });
+        }
+    }
+}
+
+/// Checks that shapes on `name` that interact with `other` are enclosed by `other`.
+///
+/// Shapes that do not interact with `other` at all are reported by
+/// [`check_required_enclosure`] if `other` is required.
+#[allow(clippy::too_many_arguments)]
+fn check_enclosure(
+    name: &str,
+    other: &str,
+    enclosure: Int,
+    one_side: Int,
+    rects: &[Rect],
+    other_rects: &[Rect],
+    violations: &mut Vec<DrcViolation>,
+) {
+    for r in rects.iter() {
+        if !other_rects.iter().any(|o| overlaps(r, o)) {
+            continue;
+        }
+
+        // Only the band around the edge of `r` needs to be covered,
+        // so that enclosing rings are handled correctly.
+        let hole = shrink(r, 1);
+        let ok = if one_side > enclosure {
+            covers(other_rects, &grow(r, one_side, enclosure), hole.as_ref())
+                || covers(other_rects, &grow(r, enclosure, one_side), hole.as_ref())
+        } else {
+            covers(other_rects, &grow(r, enclosure, enclosure), hole.as_ref())
+        };
+
+        if !ok {
+            violations.push(DrcViolation {
+                rule: DrcRule::Enclosure(other.to_string()),
+                layer: name.to_string(),
+                required: std::cmp::max(enclosure, one_side),
+                rects: vec![*r],
+            });
+        }
+    }
+}
+
+/// The sets of layers that must enclose shapes on each layer, derived from the contact stacks.
+///
+/// A shape must be covered by every layer of at least one of its sets.
+/// A cut must be covered by the other layers of its stack and by the implants
+/// drawn around it; any other stack layer must be covered by its implants.
+/// Layers that appear in a stack without any such requirement are omitted.
+fn required_enclosures(tc: &TechConfig) -> HashMap<String, Vec<Vec<String>>> {
+    let mut sets = HashMap::<String, Vec<Vec<String>>>::new();
+    for stack in tc.stacks() {
+        for (i, layer) in stack.layers.iter().enumerate() {
+            let mut set = stack
+                .implants
+                .iter()
+                .filter(|imp| &imp.around == layer)
+                .map(|imp| imp.layer.clone())
+                .collect::<Vec<_>>();
+            if i == 1 {
+                set.extend(
+                    stack
+                        .layers
+                        .iter()
+                        .enumerate()
+                        .filter(|(j, _)| *j != 1)
+                        .map(|(_, l)| l.clone()),
+                );
+            }
+            set.sort_unstable();
+            set.dedup();
+
+            let entry = sets.entry(layer.clone()).or_default();
+            if !entry.contains(&set) {
+                entry.push(set);
+            }
+        }
+    }
+    sets.retain(|_, alternatives| alternatives.iter().all(|set| !set.is_empty()));
+    sets
+}
+
+/// Checks that shapes on `name` are covered by every layer of at least one of `alternatives`.
+///
+/// Reports the layers missing from the alternative that is closest to being satisfied.
+fn check_required_enclosure(
+    name: &str,
+    tc: &TechConfig,
+    alternatives: &[Vec<String>],
+    rects: &[Rect],
+    all_rects: &HashMap<String, Vec<Rect>>,
+    violations: &mut Vec<DrcViolation>,
+) {
+    let empty = Vec::new();
+    for r in rects.iter() {
+        let missing = alternatives
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .filter(|l| !covers(all_rects.get(*l).unwrap_or(&empty), r, None))
+                    .collect::<Vec<_>>()
+            })
+            .min_by_key(Vec::len)
+            .unwrap_or_default();
+
+        for layer in missing {
+            violations.push(DrcViolation {
+                rule: DrcRule::Enclosure(layer.clone()),
+                layer: name.to_string(),
+                required: tc.layer(name).enclosure(layer),
+                rects: vec![*r],
+            });
+        }
+    }
+}
+
+/// Checks that shapes on `name` crossing shapes on `other` extend past them by `extend`.
+///
+/// Where the shapes on `other` form a ring, shapes on `name` must instead
+/// cover the hole of the ring and extend past its inner edge by `extend`.
+fn check_extension(
+    name: &str,
+    other: &str,
+    extend: Int,
+    rects: &[Rect],
+    other_rects: &[Rect],
+    violations: &mut Vec<DrcViolation>,
+) {
+    let regions = connected_regions(other_rects);
+    let mut holes = HashMap::new();
+
+    for r in rects.iter() {
+        let mut checked = HashSet::new();
+        for (o, id) in other_rects
+            .iter()
+            .zip(regions.iter())
+            .filter(|(o, _)| overlaps(r, o))
+        {
+            let region_holes = holes.entry(*id).or_insert_with(|| {
+                let members = other_rects
+                    .iter()
+                    .zip(regions.iter())
+                    .filter(|(_, r)| *r == id)
+                    .map(|(rect, _)| *rect)
+                    .collect::<Vec<_>>();
+                holes_of(&members)
+            });
+
+            let ok = if region_holes.is_empty() {
+                let x_ok = r.p0.x <= o.p0.x - extend && r.p1.x >= o.p1.x + extend;
+                let y_ok = r.p0.y <= o.p0.y - extend && r.p1.y >= o.p1.y + extend;
+                x_ok || y_ok
+            } else if checked.insert(*id) {
+                region_holes
+                    .iter()
+                    .all(|h| covers(rects, &grow(h, extend, extend), None))
+            } else {
+                continue;
+            };
+
+            if !ok {
+                violations.push(DrcViolation {
+                    rule: DrcRule::Extension(other.to_string()),
+                    layer: name.to_string(),
+                    required: extend,
+                    rects: vec![*r, *o],
+                });
+            }
+        }
+    }
+}
+
+fn derive_gates(poly: &[Rect], diff: &[Rect]) -> Vec<Rect> {
+    let mut gates = Vec::new();
+    for p in poly.iter() {
+        for d in diff.iter().filter(|d| overlaps(p, d)) {
+            gates.push(Rect::new(
+                Point::new(p.p0.x.max(d.p0.x), p.p0.y.max(d.p0.y)),
+                Point::new(p.p1.x.min(d.p1.x), p.p1.y.min(d.p1.y)),
+            ));
+        }
+    }
+    gates
+}
+
+/// Labels each rectangle with the index of the connected region it belongs to.
+fn connected_regions(rects: &[Rect]) -> Vec<usize> {
+    group(rects, |a, b| gap(a, b) == (0, 0))
+}
+
+/// Groups rectangles into the connected components of the `near` relation.
+///
+/// Returns the index of the group of each rectangle.
+fn group(rects: &[Rect], near: impl Fn(&Rect, &Rect) -> bool) -> Vec<usize> {
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[i] = root;
+        root
+    }
+
+    let mut parent = (0..rects.len()).collect::<Vec<_>>();
+    for i in 0..rects.len() {
+        for j in (i + 1)..rects.len() {
+            if near(&rects[i], &rects[j]) {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    (0..rects.len()).map(|i| find(&mut parent, i)).collect()
+}
+
+/// The horizontal and vertical distances between two rectangles.
+///
+/// Both are zero if the rectangles touch or overlap.
+pub(crate) fn gap(a: &Rect, b: &Rect) -> (Int, Int) {
+    let dx = [a.p0.x - b.p1.x, b.p0.x - a.p1.x, 0]
+        .into_iter()
+        .max()
+        .unwrap();
+    let dy = [a.p0.y - b.p1.y, b.p0.y - a.p1.y, 0]
+        .into_iter()
+        .max()
+        .unwrap();
+    (dx, dy)
+}
+
+/// Returns true if the two rectangles overlap with a positive area.
+fn overlaps(a: &Rect, b: &Rect) -> bool {
+    a.p0.x < b.p1.x && b.p0.x < a.p1.x && a.p0.y < b.p1.y && b.p0.y < a.p1.y
+}
+
+fn grow(r: &Rect, dx: Int, dy: Int) -> Rect {
+    Rect::new(
+        Point::new(r.p0.x - dx, r.p0.y - dy),
+        Point::new(r.p1.x + dx, r.p1.y + dy),
+    )
+}
+
+fn shrink(r: &Rect, d: Int) -> Option<Rect> {
+    if r.width() > 2 * d && r.height() > 2 * d {
+        Some(grow(r, -d, -d))
+    } else {
+        None
+    }
+}
+
+/// Returns true if the union of `rects` covers all of `target` outside of `hole`.
+fn covers(rects: &[Rect], target: &Rect, hole: Option<&Rect>) -> bool {
+    let (xs, ys) = grid_lines(rects, target, hole);
+
+    for x in xs.windows(2) {
+        for y in ys.windows(2) {
+            let cell = Rect::new(Point::new(x[0], y[0]), Point::new(x[1], y[1]));
+            if let Some(hole) = hole {
+                if contains(hole, &cell) {
+                    continue;
+                }
+            }
+            if !rects.iter().any(|r| contains(r, &cell)) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// The holes enclosed by the union of the given rectangles.
+///
+/// Returns the uncovered cells of the grid formed by the rectangles' edges
+/// that cannot be reached from outside the rectangles' bounding box.
+fn holes_of(rects: &[Rect]) -> Vec<Rect> {
+    let bbox = rects
+        .iter()
+        .fold(layout21::raw::BoundBox::empty(), |b, r| b.union(&r.bbox()));
+    if bbox.is_empty() {
+        return Vec::new();
+    }
+    let (xs, ys) = grid_lines(rects, &bbox.into_rect(), None);
+    let (nx, ny) = (xs.len() - 1, ys.len() - 1);
+    let cell =
+        |i: usize, j: usize| Rect::new(Point::new(xs[i], ys[j]), Point::new(xs[i + 1], ys[j + 1]));
+
+    let mut open = (0..nx)
+        .map(|i| {
+            (0..ny)
+                .map(|j| !rects.iter().any(|r| contains(r, &cell(i, j))))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut stack = Vec::new();
+    for (i, col) in open.iter().enumerate() {
+        for (j, is_open) in col.iter().enumerate() {
+            if *is_open && (i == 0 || j == 0 || i == nx - 1 || j == ny - 1) {
+                stack.push((i, j));
+            }
+        }
+    }
+
+    // Flood fill from the boundary; whatever remains open is enclosed.
+    while let Some((i, j)) = stack.pop() {
+        if !open[i][j] {
+            continue;
+        }
+        open[i][j] = false;
+        if i > 0 {
+            stack.push((i - 1, j));
+        }
+        if i + 1 < nx {
+            stack.push((i + 1, j));
+        }
+        if j > 0 {
+            stack.push((i, j - 1));
+        }
+        if j + 1 < ny {
+            stack.push((i, j + 1));
+        }
+    }
+
+    let mut holes = Vec::new();
+    for (i, col) in open.iter().enumerate() {
+        for (j, is_open) in col.iter().enumerate() {
+            if *is_open {
+                holes.push(cell(i, j));
+            }
+        }
+    }
+    holes
+}
+
+/// The area of the union of the given rectangles.
+fn union_area(rects: &[Rect]) -> Int {
+    let bbox = rects
+        .iter()
+        .fold(layout21::raw::BoundBox::empty(), |b, r| b.union(&r.bbox()));
+    if bbox.is_empty() {
+        return 0;
+    }
+    let (xs, ys) = grid_lines(rects, &bbox.into_rect(), None);
+
+    let mut area = 0;
+    for x in xs.windows(2) {
+        for y in ys.windows(2) {
+            let cell = Rect::new(Point::new(x[0], y[0]), Point::new(x[1], y[1]));
+            if rects.iter().any(|r| contains(r, &cell)) {
+                area += cell.width() * cell.height();
+            }
+        }
+    }
+    area
+}
+
+/// The sorted x and y coordinates of all rectangle edges that lie within `target`.
+fn grid_lines(rects: &[Rect], target: &Rect, hole: Option<&Rect>) -> (Vec<Int>, Vec<Int>) {
+    let mut xs = vec![target.p0.x, target.p1.x];
+    let mut ys = vec![target.p0.y, target.p1.y];
+    for r in rects.iter().chain(hole) {
+        xs.extend([r.p0.x, r.p1.x]);
+        ys.extend([r.p0.y, r.p1.y]);
+    }
+    xs.retain(|x| *x >= target.p0.x && *x <= target.p1.x);
+    ys.retain(|y| *y >= target.p0.y && *y <= target.p1.y);
+    xs.sort_unstable();
+    xs.dedup();
+    ys.sort_unstable();
+    ys.dedup();
+    (xs, ys)
+}
+
+/// Returns true if `outer` contains `inner`.
+fn contains(outer: &Rect, inner: &Rect) -> bool {
+    outer.p0.x <= inner.p0.x
+        && outer.p0.y <= inner.p0.y
+        && outer.p1.x >= inner.p1.x
+        && outer.p1.y >= inner.p1.y
+}
+
+/// Panics, listing every violation, if `cell` is not DRC clean.
+#[cfg(test)]
+pub(crate) fn assert_drc_clean(pdk: &Pdk, cell: &Ptr<Cell>) {
+    assert_no_violations(&pdk.drc(cell));
+}
+
+/// Panics, listing every violation, if `violations` is not empty.
+#[cfg(test)]
+pub(crate) fn assert_no_violations(violations: &[DrcViolation]) {
+    assert!(
+        violations.is_empty(),
+        "{} DRC violations:\n{}",
+        violations.len(),
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+#[cfg(all(test, feature = "sky130"))]
+mod tests {
+    use super::*;
+
+    fn rects(layers: &[(&str, Vec<Rect>)]) -> HashMap<String, Vec<Rect>> {
+        layers
+            .iter()
+            .map(|(name, rects)| (name.to_string(), rects.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_drc_clean() {
+        let tc = crate::tech::sky130::tech_config();
+        let m1 = vec![
+            Rect::new(Point::new(0, 0), Point::new(300, 300)),
+            Rect::new(Point::new(300, 0), Point::new(600, 300)),
+            Rect::new(Point::new(800, 0), Point::new(1100, 300)),
+        ];
+        assert_eq!(check(&tc, rects(&[("m1", m1)])), vec![]);
+    }
+
+    #[test]
+    fn test_drc_width_space_area() {
+        let tc = crate::tech::sky130::tech_config();
+        let narrow = Rect::new(Point::new(0, 0), Point::new(100, 1000));
+        let a = Rect::new(Point::new(500, 0), Point::new(800, 300));
+        let b = Rect::new(Point::new(900, 0), Point::new(1100, 300));
+        let violations = check(&tc, rects(&[("m1", vec![narrow, a, b])]));
+
+        let rules = violations.iter().map(|v| &v.rule).collect::<Vec<_>>();
+        assert!(rules.contains(&&DrcRule::Width));
+        assert!(rules.contains(&&DrcRule::Space));
+        assert!(rules.contains(&&DrcRule::Area));
+        assert!(violations.iter().all(|v| v.layer == "m1"));
+    }
+
+    #[test]
+    fn test_drc_array_spacing() {
+        let tc = crate::tech::sky130::tech_config();
+        let cfg = tc.layer("via");
+        let array = |n: Int, space: Int| {
+            let pitch = cfg.width + space;
+            (0..n * n)
+                .map(|i| {
+                    let p0 = Point::new((i % n) * pitch, (i / n) * pitch);
+                    Rect::new(p0, Point::new(p0.x + cfg.width, p0.y + cfg.width))
+                })
+                .collect::<Vec<_>>()
+        };
+        let is_space = |v: &DrcViolation| v.rule == DrcRule::Space && v.layer == "via";
+
+        let small = check(&tc, rects(&[("via", array(3, cfg.space))]));
+        assert!(!small.iter().any(is_space));
+        let large = check(&tc, rects(&[("via", array(4, cfg.space))]));
+        assert!(large.iter().any(is_space));
+        let large = check(&tc, rects(&[("via", array(4, cfg.cut_space(4, 4)))]));
+        assert!(!large.iter().any(is_space));
+    }
+
+    #[test]
+    fn test_drc_enclosure() {
+        let tc = crate::tech::sky130::tech_config();
+        let mcon = Rect::new(Point::new(0, 0), Point::new(170, 170));
+        let m1 = Rect::new(Point::new(-30, -60), Point::new(200, 230));
+        assert!(check(
+            &tc,
+            rects(&[("mcon", vec![mcon]), ("m1", vec![m1]), ("li", vec![m1])])
+        )
+        .iter()
+        .all(|v| v.rule != DrcRule::Enclosure("m1".to_string())));
+
+        let m1 = Rect::new(Point::new(-10, -60), Point::new(200, 230));
+        assert!(check(&tc, rects(&[("mcon", vec![mcon]), ("m1", vec![m1])]))
+            .iter()
+            .any(|v| v.rule == DrcRule::Enclosure("m1".to_string()) && v.layer == "mcon"));
+    }
+
+    #[test]
+    fn test_drc_missing_enclosure() {
+        let tc = crate::tech::sky130::tech_config();
+        let licon = Rect::new(Point::new(0, 0), Point::new(170, 170));
+        let is_enc = |v: &DrcViolation, layer: &str| {
+            v.rule == DrcRule::Enclosure(layer.to_string()) && v.layer == "licon"
+        };
+
+        let violations = check(&tc, rects(&[("licon", vec![licon])]));
+        assert!(violations.iter().any(|v| is_enc(v, "li")));
+
+        let li = Rect::new(Point::new(0, -80), Point::new(170, 250));
+        let diff = Rect::new(Point::new(-60, -60), Point::new(230, 230));
+        let violations = check(
+            &tc,
+            rects(&[
+                ("licon", vec![licon]),
+                ("li", vec![li]),
+                ("diff", vec![diff]),
+            ]),
+        );
+        assert!(!violations
+            .iter()
+            .any(|v| is_enc(v, "li") || is_enc(v, "diff")));
+        assert!(violations
+            .iter()
+            .any(|v| v.layer == "diff" && v.rule == DrcRule::Enclosure("nsdm".to_string())));
+    }
+
+    #[test]
+    fn test_drc_ring_extension() {
+        let tc = crate::tech::sky130::tech_config();
+        let extend = tc.layer("dnwell").extension("nwell");
+        let ring = vec![
+            Rect::new(Point::new(0, 0), Point::new(10_000, 2_000)),
+            Rect::new(Point::new(0, 8_000), Point::new(10_000, 10_000)),
+            Rect::new(Point::new(0, 2_000), Point::new(2_000, 8_000)),
+            Rect::new(Point::new(8_000, 2_000), Point::new(10_000, 8_000)),
+        ];
+        let is_ext = |v: &DrcViolation| v.rule == DrcRule::Extension("nwell".to_string());
+
+        let dnwell = Rect::new(
+            Point::new(2_000 - extend, 2_000 - extend),
+            Point::new(8_000 + extend, 8_000 + extend),
+        );
+        let violations = check(
+            &tc,
+            rects(&[("nwell", ring.clone()), ("dnwell", vec![dnwell])]),
+        );
+        assert!(!violations.iter().any(is_ext));
+
+        let dnwell = Rect::new(Point::new(1_500, 1_500), Point::new(8_500, 8_500));
+        let violations = check(&tc, rects(&[("nwell", ring), ("dnwell", vec![dnwell])]));
+        assert!(violations.iter().any(is_ext));
+    }
+
+    #[test]
+    fn test_drc_gate_spacing() {
+        let tc = crate::tech::sky130::tech_config();
+        let diff = Rect::new(Point::new(0, 0), Point::new(1000, 1000));
+        let poly = Rect::new(Point::new(-130, 400), Point::new(1130, 550));
+        let licon = Rect::new(Point::new(400, 600), Point::new(570, 770));
+        let violations = check(
+            &tc,
+            rects(&[
+                ("diff", vec![diff]),
+                ("poly", vec![poly]),
+                ("licon", vec![licon]),
+            ]),
+        );
+        assert!(violations
+            .iter()
+            .any(|v| v.rule == DrcRule::Spacing("licon".to_string()) && v.layer == GATE_LAYER));
+    }
+}
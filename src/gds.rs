@@ -49,7 +49,7 @@ pub fn load_gds(pdk_lib: &mut PdkLib, gds_file: impl AsRef<Path>) -> LayoutResul
         let new_cell = cell.read().unwrap();
 
         if !map.contains_key(&new_cell.name) {
-            pdk_lib.lib.cells.push(Ptr::clone(cell));
+            pdk_lib.add_cell(cell);
             map.insert(new_cell.name.clone(), Ptr::clone(cell));
         }
     }
@@ -1,5 +1,6 @@
 use crate::config::Int;
-use layout21::raw::{BoundBox, Point, Rect};
+use crate::{Error, PdkResult};
+use layout21::raw::{BoundBox, Dir, Instance, Point, Rect, Shape};
 
 pub fn box_width(b: &mut Rect) -> Int {
     b.p1.x - b.p0.x
@@ -53,9 +54,133 @@ pub fn rect_from_bbox(bbox: &BoundBox) -> Rect {
     }
 }
 
+/// Grows `b` equally on both sides along `dir` until its area is at least `area`.
+///
+/// The added length is a multiple of `grid`.
+pub fn grow_to_area(b: &mut Rect, area: Int, dir: Dir, grid: Int) {
+    let (lo, hi, other) = match dir {
+        Dir::Horiz => (&mut b.p0.x, &mut b.p1.x, b.p1.y - b.p0.y),
+        Dir::Vert => (&mut b.p0.y, &mut b.p1.y, b.p1.x - b.p0.x),
+    };
+    if other <= 0 {
+        return;
+    }
+    let len = (area + other - 1) / other;
+    let extra = len - (*hi - *lo);
+    if extra > 0 {
+        let extra = (extra + grid - 1) / grid * grid;
+        let half = round_down(extra / 2, grid);
+        *lo -= half;
+        *hi += extra - half;
+    }
+}
+
+/// Rounds `x` down to a multiple of `grid`.
+pub(crate) fn round_down(x: Int, grid: Int) -> Int {
+    x.div_euclid(grid) * grid
+}
+
 pub fn translate(r: &Rect, p: &Point) -> Rect {
     Rect {
         p0: Point::new(r.p0.x + p.x, r.p0.y + p.y),
         p1: Point::new(r.p1.x + p.x, r.p1.y + p.y),
     }
 }
+
+/// Reflects `r` across the line `y = x`.
+pub fn transpose(r: &Rect) -> Rect {
+    Rect {
+        p0: Point::new(r.p0.y, r.p0.x),
+        p1: Point::new(r.p1.y, r.p1.x),
+    }
+}
+
+/// Reflects the given shape across the line `y = x`.
+///
+/// Only rectangles are supported; other shapes return [`Error::UnsupportedTranspose`].
+pub(crate) fn transpose_shape(shape: &mut Shape) -> PdkResult<()> {
+    match shape {
+        Shape::Rect(ref mut r) => *r = transpose(r),
+        _ => {
+            return Err(Error::UnsupportedTranspose(
+                "non-rectangular shape".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Reflects the given instance across the line `y = x`.
+///
+/// Reflecting about the x-axis, then rotating by 90 degrees counterclockwise,
+/// maps `(x, y)` to `(y, x)`. Instances that are already reflected or rotated
+/// return [`Error::UnsupportedTranspose`].
+pub(crate) fn transpose_inst(inst: &mut Instance) -> PdkResult<()> {
+    if inst.reflect_vert || inst.angle.is_some() {
+        return Err(Error::UnsupportedTranspose(format!(
+            "reflected or rotated instance {}",
+            inst.inst_name
+        )));
+    }
+    inst.loc = Point::new(inst.loc.y, inst.loc.x);
+    inst.reflect_vert = true;
+    inst.angle = Some(90.);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use layout21::raw::Cell;
+    use layout21::utils::Ptr;
+
+    use super::*;
+
+    #[test]
+    fn test_transpose_inst() {
+        let mut inst = Instance {
+            inst_name: "inst".to_string(),
+            cell: Ptr::new(Cell {
+                name: "cell".to_string(),
+                abs: None,
+                layout: None,
+            }),
+            loc: Point::new(10, 20),
+            reflect_vert: false,
+            angle: None,
+        };
+        transpose_inst(&mut inst).unwrap();
+        assert_eq!(inst.loc, Point::new(20, 10));
+        assert!(inst.reflect_vert);
+        assert_eq!(inst.angle, Some(90.));
+
+        assert!(matches!(
+            transpose_inst(&mut inst),
+            Err(Error::UnsupportedTranspose(_))
+        ));
+    }
+
+    #[test]
+    fn test_grow_to_area() {
+        // Rounds the added length up to the grid, splitting it between both sides.
+        let mut b = Rect::new(Point::new(0, 0), Point::new(200, 200));
+        grow_to_area(&mut b, 83_000, Dir::Horiz, 5);
+        assert_eq!(b, Rect::new(Point::new(-105, 0), Point::new(310, 200)));
+        assert!(b.width() * b.height() >= 83_000);
+
+        let mut b = Rect::new(Point::new(0, 0), Point::new(100, 100));
+        grow_to_area(&mut b, 15_000, Dir::Vert, 10);
+        assert_eq!(b, Rect::new(Point::new(0, -20), Point::new(100, 130)));
+
+        // Rectangles that already meet the area, or have no extent
+        // in the other direction, are left alone.
+        let legal = Rect::new(Point::new(0, 0), Point::new(300, 300));
+        let mut b = legal;
+        grow_to_area(&mut b, 83_000, Dir::Horiz, 5);
+        assert_eq!(b, legal);
+
+        let empty = Rect::new(Point::new(0, 0), Point::new(200, 0));
+        let mut b = empty;
+        grow_to_area(&mut b, 83_000, Dir::Horiz, 5);
+        assert_eq!(b, empty);
+    }
+}
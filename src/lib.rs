@@ -1,18 +1,21 @@
-use std::{collections::HashMap, path::Path};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 use arcstr::ArcStr;
 use config::TechConfig;
 use contact::{Contact, ContactParams};
 use layout21::gds21::GdsError;
-use layout21::raw::{LayoutError, Units};
+use layout21::raw::{Dir, LayoutError, Rect, Units};
 use layout21::{
     gds21::GdsLibrary,
     raw::{Cell, DepOrder, LayerKey, Layers, LayoutResult, Library},
     utils::{Ptr, PtrList},
 };
 use mos::{LayoutTransistors, MosParams, MosResult};
+use tech::Technology;
+use via::{ViaStack, ViaStackParams};
 
-use crate::config::Int;
+use crate::config::{Int, Uint};
 
 pub type Ref<T> = std::sync::Arc<T>;
 pub type LayerIdx = u32;
@@ -20,17 +23,21 @@ pub type LayerIdx = u32;
 pub mod bus;
 pub mod config;
 pub mod contact;
+pub mod drc;
 pub mod gds;
 pub mod geometry;
 pub mod mos;
 pub mod tech;
+pub mod via;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Pdk {
     pub tech: ArcStr,
     pub config: Ptr<TechConfig>,
     pub layers: Ptr<Layers>,
+    technology: Option<Ref<dyn Technology>>,
     contacts: Ptr<HashMap<ContactParams, Ref<Contact>>>,
+    via_stacks: Ptr<HashMap<ViaStackParams, Ref<ViaStack>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,6 +46,8 @@ pub struct PdkLib {
     pub pdk: Pdk,
     pub lib: Library,
     ptx: HashMap<MosParams, Ref<LayoutTransistors>>,
+    /// The cells that have been added to `lib`.
+    registered: HashSet<Ptr<Cell>>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -49,8 +58,28 @@ pub enum Error {
     Gds(#[from] GdsError),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("unknown layer: {0}")]
+    UnknownLayer(String),
+    #[error("unknown contact stack: {0}")]
+    UnknownStack(String),
+    #[error("unsupported technology: {0}")]
+    UnsupportedTech(ArcStr),
+    #[error("invalid contact stack: {0}")]
+    InvalidStack(String),
+    #[error("contact enclosure on {0} violates the enclosure rules")]
+    InvalidEnclosure(String),
+    #[error("invalid contact size: {rows}x{cols}")]
+    InvalidContactSize { rows: Uint, cols: Uint },
+    #[error("invalid via stack from metal {from} to metal {to}")]
+    InvalidViaStack { from: LayerIdx, to: LayerIdx },
+    #[error("rects do not overlap")]
+    NoOverlap,
+    #[error("cannot transpose {0}")]
+    UnsupportedTranspose(String),
 }
 
+pub type PdkResult<T> = std::result::Result<T, Error>;
+
 impl PdkLib {
     /// Exports this library to GDS structures.
     ///
@@ -82,10 +111,44 @@ impl PdkLib {
         self.lib.cell(name)
     }
 
+    /// Adds `cell` to the library, along with every cell it instances.
+    ///
+    /// Cells that have already been added are skipped.
+    pub fn add_cell(&mut self, cell: &Ptr<Cell>) {
+        if self.registered.contains(cell) {
+            return;
+        }
+        let deps = cell
+            .read()
+            .unwrap()
+            .layout
+            .as_ref()
+            .map(|layout| {
+                layout
+                    .insts
+                    .iter()
+                    .map(|inst| Ptr::clone(&inst.cell))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        for dep in deps.iter() {
+            self.add_cell(dep);
+        }
+        self.registered.insert(Ptr::clone(cell));
+        self.lib.cells.push(Ptr::clone(cell));
+    }
+
     pub fn draw_contact(&mut self, params: &ContactParams) -> Ref<Contact> {
-        let ct = self.pdk.draw_contact(params);
-        self.lib.cells.push(ct.cell.clone());
-        ct
+        self.try_draw_contact(params)
+            .unwrap_or_else(|err| panic!("failed to draw contact {}: {}", params, err))
+    }
+
+    /// Like [`PdkLib::draw_contact`], but returns an error instead of panicking
+    /// if the contact cannot be drawn.
+    pub fn try_draw_contact(&mut self, params: &ContactParams) -> PdkResult<Ref<Contact>> {
+        let ct = self.pdk.try_get_contact(params)?;
+        self.add_cell(&ct.cell);
+        Ok(ct)
     }
 
     pub fn draw_mos(&mut self, params: MosParams) -> MosResult<Ref<LayoutTransistors>> {
@@ -93,11 +156,9 @@ impl PdkLib {
             return Ok(ptx.clone());
         }
 
-        let ptx = match &*self.tech {
-            "sky130" => self.pdk.draw_sky130_mos(params.clone()),
-            _ => panic!("unsupported technology: {}", &self.tech),
-        }?;
+        let ptx = self.pdk.technology()?.draw_mos(&self.pdk, params.clone())?;
 
+        self.add_cell(&ptx.cell);
         self.ptx.insert(params, ptx.clone());
 
         Ok(ptx)
@@ -118,17 +179,117 @@ fn round(a: Int, b: Int) -> Int {
 }
 
 impl Pdk {
+    /// Creates a new [`Pdk`] for one of the technologies built into this crate.
+    ///
+    /// If `tech` does not name a built-in technology, the returned [`Pdk`]
+    /// can still be used to query the config, but drawing cells
+    /// will fail with [`Error::UnsupportedTech`].
     pub fn new(tech: ArcStr, config: TechConfig) -> LayoutResult<Self> {
+        let technology = tech::builtin(&tech);
+        Self::new_inner(tech, technology, config)
+    }
+
+    /// Creates a new [`Pdk`] backed by the given [`Technology`].
+    pub fn with_technology(
+        technology: Ref<dyn Technology>,
+        config: TechConfig,
+    ) -> LayoutResult<Self> {
+        let tech = ArcStr::from(technology.name());
+        Self::new_inner(tech, Some(technology), config)
+    }
+
+    fn new_inner(
+        tech: ArcStr,
+        technology: Option<Ref<dyn Technology>>,
+        config: TechConfig,
+    ) -> LayoutResult<Self> {
         let layers = Ptr::new(config.get_layers()?);
         let config = Ptr::new(config);
         Ok(Self {
             tech,
             config,
             layers,
+            technology,
             contacts: Ptr::new(HashMap::new()),
+            via_stacks: Ptr::new(HashMap::new()),
         })
     }
 
+    /// The [`Technology`] used to draw cells.
+    pub fn technology(&self) -> PdkResult<Ref<dyn Technology>> {
+        self.technology
+            .clone()
+            .ok_or_else(|| Error::UnsupportedTech(self.tech.clone()))
+    }
+
+    pub(crate) fn draw_contact(&self, params: &ContactParams) -> PdkResult<Ref<Contact>> {
+        self.technology()?.draw_contact(self, params)
+    }
+
+    pub fn metal_name(&self, i: LayerIdx) -> &'static str {
+        self.try_metal_name(i)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_metal_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        self.technology()?.metal_name(i)
+    }
+
+    /// The name of the via layer connecting metal `i+1` to metal `i`.
+    pub fn via_name(&self, i: LayerIdx) -> &'static str {
+        self.try_via_name(i).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_via_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        self.technology()?.via_name(i)
+    }
+
+    /// The name of the stack connecting metal `i+1` to metal `i`.
+    pub fn stack_name(&self, layer: LayerIdx) -> &'static str {
+        self.try_stack_name(layer)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_stack_name(&self, layer: LayerIdx) -> PdkResult<&'static str> {
+        self.technology()?.stack_name(layer)
+    }
+
+    /// The names of all metal layers, from the lowest to the highest.
+    pub(crate) fn metal_names(&self) -> Vec<&'static str> {
+        (0..).map_while(|i| self.try_metal_name(i).ok()).collect()
+    }
+
+    /// Grows `rect` on `layer` equally on both sides along `dir`
+    /// until it meets the layer's minimum area rule.
+    pub fn legalize_area(&self, layer: LayerKey, rect: Rect, dir: Dir) -> Rect {
+        self.try_legalize_area(layer, rect, dir)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`Pdk::legalize_area`], but returns an error if `layer` is not in the config.
+    pub fn try_legalize_area(&self, layer: LayerKey, mut rect: Rect, dir: Dir) -> PdkResult<Rect> {
+        let tc = self.config.read().unwrap();
+        let area = tc.try_layer(&self.layer_name(layer))?.area;
+        geometry::grow_to_area(&mut rect, area, dir, tc.grid);
+        Ok(rect)
+    }
+
+    pub fn metal(&self, i: LayerIdx) -> LayerKey {
+        self.try_metal(i).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_metal(&self, i: LayerIdx) -> PdkResult<LayerKey> {
+        self.try_get_layerkey(self.try_metal_name(i)?)
+    }
+
+    pub fn via(&self, i: LayerIdx) -> LayerKey {
+        self.try_via(i).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_via(&self, i: LayerIdx) -> PdkResult<LayerKey> {
+        self.try_get_layerkey(self.try_via_name(i)?)
+    }
+
     pub fn create_lib(&self, name: impl Into<String>) -> Library {
         Library {
             name: name.into(),
@@ -144,6 +305,7 @@ impl Pdk {
             lib: self.create_lib(name),
             pdk: self.clone(),
             ptx: HashMap::new(),
+            registered: HashSet::new(),
         }
     }
 
@@ -197,6 +359,21 @@ impl Pdk {
         let layers = self.layers.read().unwrap();
         layers.keyname(layer)
     }
+
+    /// Like [`Pdk::get_layerkey`], but returns an error if there is no layer named `layer`.
+    pub fn try_get_layerkey(&self, layer: &str) -> PdkResult<LayerKey> {
+        self.get_layerkey(layer)
+            .ok_or_else(|| Error::UnknownLayer(layer.to_string()))
+    }
+
+    /// The name of the layer with the given key, for use in error messages.
+    pub(crate) fn layer_name(&self, key: LayerKey) -> String {
+        let layers = self.layers.read().unwrap();
+        layers
+            .get(key)
+            .and_then(|l| l.name.clone())
+            .unwrap_or_else(|| format!("{:?}", key))
+    }
 }
 
 // #[cfg(test)]
@@ -1,16 +1,26 @@
 use std::fmt::Write;
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
 
 use layout21::raw::geom::Dir;
 use layout21::raw::{BoundBox, BoundBoxTrait};
 use layout21::{
-    raw::{AbstractPort, Cell, LayerKey, LayoutError, Rect},
+    raw::{
+        AbstractPort, Cell, Element, Instance, LayerKey, LayerPurpose, LayoutError, Point, Rect,
+        Shape,
+    },
     utils::Ptr,
 };
 
 use serde::{Deserialize, Serialize};
 
 use crate::config::{Int, Uint};
+use crate::contact::{Alignment, ContactParams};
+use crate::drc::gap;
+use crate::geometry::{rect_from_bbox, round_down, transpose, transpose_inst, transpose_shape};
+use crate::{LayerIdx, Pdk, PdkResult};
 
 /// MOSFET Types
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -104,6 +114,38 @@ pub struct MosDevice {
     pub skip_sd_metal: Vec<usize>,
 }
 
+/// Assigns nets to the source/drain regions of a [`MosDevice`].
+///
+/// See [`MosParams::sd_nets`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SdNets {
+    /// Even regions connect to `even`, and odd regions connect to `odd`.
+    Alternating { even: String, odd: String },
+    /// Region `i` connects to the `i`th net.
+    ///
+    /// Must have one net per source/drain region.
+    PerRegion(Vec<String>),
+}
+
+impl SdNets {
+    /// The net connected to source/drain region `i`.
+    pub fn net(&self, i: usize) -> Option<&str> {
+        match self {
+            Self::Alternating { even, odd } => Some(if i % 2 == 1 { odd } else { even }),
+            Self::PerRegion(nets) => nets.get(i).map(String::as_str),
+        }
+    }
+}
+
+impl Display for SdNets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Alternating { even, odd } => write!(f, "{}_{}", even, odd),
+            Self::PerRegion(nets) => write!(f, "{}", nets.join("_")),
+        }
+    }
+}
+
 impl MosDevice {
     pub fn builder() -> MosDeviceBuilder {
         MosDeviceBuilder::default()
@@ -120,6 +162,29 @@ impl MosDevice {
         self.skip_sd_metal.push(idx);
         self
     }
+
+    /// The effective width of the device, summed over all fingers.
+    #[inline]
+    pub fn total_width(&self) -> Int {
+        self.width * self.fingers
+    }
+}
+
+/// The required parity of the number of fingers of folded devices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FingerParity {
+    /// Any number of fingers.
+    Any,
+    /// An even number of fingers, so that both outer regions are sources.
+    Even,
+    /// An odd number of fingers.
+    Odd,
+}
+
+impl Default for FingerParity {
+    fn default() -> Self {
+        Self::Any
+    }
 }
 
 /// Specifies the geometric arrangement of contacts for transistor gates.
@@ -141,6 +206,37 @@ impl Default for GateContactStrategy {
     }
 }
 
+impl GateContactStrategy {
+    /// Determines the side on which each of the `nf` gate contacts is placed.
+    ///
+    /// Returns [`MosError::BadParams`] for [`GateContactStrategy::Other`].
+    pub(crate) fn sides(&self, nf: usize) -> MosResult<Vec<GateSide>> {
+        let side = |left: bool| {
+            if left {
+                GateSide::Left
+            } else {
+                GateSide::Right
+            }
+        };
+        match self {
+            Self::SingleSide => Ok(vec![GateSide::Left; nf]),
+            Self::Alternate => Ok((0..nf).map(|i| side(i % 2 == 0)).collect()),
+            Self::Abba => Ok((0..nf).map(|i| side(i % 4 == 0 || i % 4 == 3)).collect()),
+            Self::Other(s) => Err(MosError::BadParams(format!(
+                "unsupported gate contact strategy: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// The side of the gate poly on which a gate contact is placed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum GateSide {
+    Left,
+    Right,
+}
+
 /// Parameters for generating MOSFET layouts
 ///
 /// When multiple devices are given, they will be drawn
@@ -164,6 +260,36 @@ pub struct MosParams {
 
     /// Specifies how to place gate contacts
     pub contact_strategy: GateContactStrategy,
+
+    /// If true, draw well/substrate tap strips on both ends of each device.
+    ///
+    /// May not be supported by all processes.
+    #[builder(default)]
+    pub taps: bool,
+
+    /// If set, strap source/drain and gate pins up to this metal.
+    ///
+    /// Metal 0 is the layer the generator contacts sources, drains and gates on.
+    /// The pins and ports of the resulting [`LayoutTransistors`] are on the strap metal.
+    #[builder(default)]
+    pub strap: Option<LayerIdx>,
+
+    /// The number of dummy fingers to draw on each side of the real fingers.
+    ///
+    /// Dummy gates are drawn with the same spacing as real gates, and are
+    /// contacted so that they can be tied off through their own ports.
+    ///
+    /// May not be supported by all processes.
+    #[builder(default)]
+    pub dummies: Uint,
+
+    /// The nets connected to the source/drain regions of each device, by device index.
+    ///
+    /// Regions of a device on the same net are shorted together, and the
+    /// resulting ports are named `{net}_{j}` for device `j` rather than by region.
+    /// Devices without an entry keep one port per region.
+    #[builder(default)]
+    pub sd_nets: BTreeMap<usize, SdNets>,
 }
 
 impl MosParams {
@@ -175,8 +301,38 @@ impl MosParams {
         let mut name = String::new();
         write!(&mut name, "ptx").unwrap();
 
-        for device in self.devices.iter() {
+        for (j, device) in self.devices.iter().enumerate() {
             write!(&mut name, "__{}", device.name()).unwrap();
+            if let Some(nets) = self.sd_nets.get(&j) {
+                write!(&mut name, "_{}", nets).unwrap();
+            }
+        }
+
+        match self.contact_strategy {
+            GateContactStrategy::SingleSide => {}
+            GateContactStrategy::Alternate => write!(&mut name, "_alt").unwrap(),
+            GateContactStrategy::Abba => write!(&mut name, "_abba").unwrap(),
+            GateContactStrategy::Other(ref s) => write!(&mut name, "_{}", s).unwrap(),
+        }
+
+        if self.dnw {
+            write!(&mut name, "_dnw").unwrap();
+        }
+
+        if self.taps {
+            write!(&mut name, "_taps").unwrap();
+        }
+
+        if self.direction == Dir::Vert {
+            write!(&mut name, "_vert").unwrap();
+        }
+
+        if let Some(metal) = self.strap {
+            write!(&mut name, "_strap{}", metal).unwrap();
+        }
+
+        if self.dummies > 0 {
+            write!(&mut name, "_dummies{}", self.dummies).unwrap();
         }
 
         name
@@ -193,6 +349,21 @@ impl MosParams {
         self
     }
     #[inline]
+    pub fn taps(&mut self, taps: bool) -> &mut Self {
+        self.taps = taps;
+        self
+    }
+    #[inline]
+    pub fn strap(&mut self, metal: LayerIdx) -> &mut Self {
+        self.strap = Some(metal);
+        self
+    }
+    #[inline]
+    pub fn dummies(&mut self, dummies: Uint) -> &mut Self {
+        self.dummies = dummies;
+        self
+    }
+    #[inline]
     pub fn contact_strategy(&mut self, contact_strategy: GateContactStrategy) -> &mut Self {
         self.contact_strategy = contact_strategy;
         self
@@ -203,6 +374,76 @@ impl MosParams {
         self
     }
 
+    /// Assigns `nets` to the source/drain regions of device `j`.
+    pub fn sd_nets(&mut self, j: usize, nets: SdNets) -> &mut Self {
+        self.sd_nets.insert(j, nets);
+        self
+    }
+
+    /// Creates parameters for `devices`, folding each of them into the same number of fingers.
+    ///
+    /// Each device is paired with its requested total width. The `width` and `fingers`
+    /// of the devices are replaced by the folded finger width and number of fingers.
+    /// The devices are given the fewest fingers with the requested `parity` such that
+    /// no finger is wider than `max_finger_width`. Finger widths are rounded to the
+    /// nearest multiple of [`Pdk::grid`], so the effective width reported by
+    /// [`MosDevice::total_width`] may differ slightly from the requested width.
+    ///
+    /// Returns [`MosError::BadParams`] if some device's fingers would be narrower
+    /// than the minimum diffusion width.
+    pub fn folded(
+        pdk: &Pdk,
+        devices: Vec<(MosDevice, Int)>,
+        max_finger_width: Int,
+        parity: FingerParity,
+    ) -> MosResult<Self> {
+        if devices.is_empty() {
+            return Err(MosError::NoDevices);
+        }
+        let grid = pdk.grid();
+        let max_finger_width = max_finger_width / grid * grid;
+        if max_finger_width <= 0 {
+            return Err(MosError::BadParams(format!(
+                "invalid maximum finger width: {}",
+                max_finger_width
+            )));
+        }
+        if let Some((j, (_, total))) = devices.iter().enumerate().find(|(_, (_, w))| *w <= 0) {
+            return Err(MosError::BadParams(format!(
+                "device {} has invalid total width: {}",
+                j, total
+            )));
+        }
+
+        let mut fingers = devices
+            .iter()
+            .map(|(_, total)| (total + max_finger_width - 1) / max_finger_width)
+            .max()
+            .unwrap();
+        match parity {
+            FingerParity::Even if fingers % 2 == 1 => fingers += 1,
+            FingerParity::Odd if fingers % 2 == 0 => fingers += 1,
+            _ => {}
+        }
+
+        let min_width = pdk.config.read().unwrap().try_layer("diff")?.width;
+        let mut params = Self::new();
+        for (j, (mut d, total)) in devices.into_iter().enumerate() {
+            let width = (total + fingers * grid / 2) / (fingers * grid) * grid;
+            let width = std::cmp::min(width, max_finger_width);
+            if width <= 0 || width < min_width {
+                return Err(MosError::BadParams(format!(
+                    "device {} is too narrow to fold into {} fingers of width {} (minimum {})",
+                    j, fingers, width, min_width
+                )));
+            }
+            d.width = width;
+            d.fingers = fingers;
+            params.add_device(d);
+        }
+        Ok(params)
+    }
+
     pub fn validate(&self) -> Result<(), MosError> {
         if self.devices.is_empty() {
             return Err(MosError::NoDevices);
@@ -212,6 +453,12 @@ impl MosParams {
         if start.fingers <= 0 {
             return Err(MosError::InvalidNumFingers(start.fingers));
         }
+        if self.dummies < 0 {
+            return Err(MosError::BadParams(format!(
+                "invalid number of dummy fingers: {}",
+                self.dummies
+            )));
+        }
 
         for device in self.devices.iter().skip(1) {
             if device.length != start.length {
@@ -221,6 +468,22 @@ impl MosParams {
             }
         }
 
+        for (&j, nets) in self.sd_nets.iter() {
+            let device = self.devices.get(j).ok_or_else(|| {
+                MosError::BadParams(format!("source/drain nets given for missing device {}", j))
+            })?;
+            if let SdNets::PerRegion(ref nets) = nets {
+                if nets.len() as Uint != device.fingers + 1 {
+                    return Err(MosError::BadParams(format!(
+                        "device {} has {} source/drain nets, but {} source/drain regions",
+                        j,
+                        nets.len(),
+                        device.fingers + 1
+                    )));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -274,6 +537,19 @@ pub struct LayoutTransistors {
     ///
     /// Note that this is NOT the number of devices.
     pub num_fingers: usize,
+
+    /// A collection of the positions of the dummy gate pins.
+    ///
+    /// `dummy_pins[k]` is the metal region corresponding to the `k`'th
+    /// dummy finger, counting from the bottom-most (or left-most) one.
+    /// Empty if no dummy fingers were drawn.
+    pub dummy_pins: Vec<Rect>,
+
+    /// The source/drain pins connected to each net.
+    ///
+    /// `nets[i]` maps the nets of device `i` to its pins on that net.
+    /// Only contains nets assigned with [`MosParams::sd_nets`].
+    pub nets: Vec<HashMap<String, Vec<Rect>>>,
 }
 
 impl LayoutTransistors {
@@ -290,12 +566,33 @@ impl LayoutTransistors {
         self.get_port(&format!("sd_{}_{}", i, j))
     }
 
+    /// The tie-off port of dummy finger `k`, if dummy fingers were drawn.
+    pub fn dummy_port(&self, k: Uint) -> Option<AbstractPort> {
+        assert!(k >= 0);
+
+        self.get_port(&format!("dummy_{}", k))
+    }
+
     pub fn vpb_port(&self, i: Uint) -> Option<AbstractPort> {
         assert!(i >= 0);
 
         self.get_port(&format!("vpb_{}", i))
     }
 
+    /// The n-well taps of device `i`, if taps were drawn and device `i` is a PMOS.
+    pub fn vpb_tap_port(&self, i: Uint) -> Option<AbstractPort> {
+        assert!(i >= 0);
+
+        self.get_port(&format!("vpb_tap_{}", i))
+    }
+
+    /// The substrate taps of device `i`, if taps were drawn and device `i` is an NMOS.
+    pub fn vnb_tap_port(&self, i: Uint) -> Option<AbstractPort> {
+        assert!(i >= 0);
+
+        self.get_port(&format!("vnb_tap_{}", i))
+    }
+
     pub fn merged_vpb_port(&self, start: Uint) -> AbstractPort {
         let mut i = start;
         let mut bbox = BoundBox::empty();
@@ -313,6 +610,16 @@ impl LayoutTransistors {
         port
     }
 
+    /// The n-well ring surrounding the deep n-well, if one was drawn.
+    pub fn nwell_ring_port(&self) -> Option<AbstractPort> {
+        self.get_port("nwell_ring")
+    }
+
+    /// The deep n-well region, if one was drawn.
+    pub fn dnwell_port(&self) -> Option<AbstractPort> {
+        self.get_port("dnwell")
+    }
+
     fn get_port(&self, name: &str) -> Option<AbstractPort> {
         let cell = self.cell.read().unwrap();
         let abs = cell.abs.as_ref().unwrap();
@@ -331,6 +638,73 @@ impl LayoutTransistors {
 
         self.gate_pins.get(i as usize).copied()
     }
+
+    /// The source/drain pins of device `i` connected to `net`,
+    /// on [`LayoutTransistors::sd_metal`].
+    ///
+    /// Returns an empty slice if no source/drain region of device `i` is on `net`.
+    pub fn net_pins(&self, i: Uint, net: &str) -> &[Rect] {
+        assert!(i >= 0);
+
+        self.nets
+            .get(i as usize)
+            .and_then(|nets| nets.get(net))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The port for `net` on device `i`, including the pins of all regions
+    /// of device `i` on `net` and the strap shorting them together.
+    ///
+    /// Devices are not shorted to each other, so a net spanning
+    /// several devices has one port per device.
+    pub fn net_port(&self, i: Uint, net: &str) -> Option<AbstractPort> {
+        assert!(i >= 0);
+
+        self.get_port(&format!("{}_{}", net, i))
+    }
+
+    /// Reflects the transistors, including all of their pins, across the line `y = x`.
+    ///
+    /// Generators draw transistors with horizontal gates, and call this
+    /// to obtain vertical gates.
+    pub(crate) fn transpose(&mut self) -> PdkResult<()> {
+        {
+            let mut cell = self.cell.write().unwrap();
+            if let Some(ref mut layout) = cell.layout {
+                for elem in layout.elems.iter_mut() {
+                    transpose_shape(&mut elem.inner)?;
+                }
+                for inst in layout.insts.iter_mut() {
+                    transpose_inst(inst)?;
+                }
+            }
+            if let Some(ref mut abs) = cell.abs {
+                for port in abs.ports.iter_mut() {
+                    for shape in port.shapes.values_mut().flatten() {
+                        transpose_shape(shape)?;
+                    }
+                }
+            }
+        }
+        for pin in self
+            .sd_pins
+            .iter_mut()
+            .flat_map(|p| p.values_mut())
+            .flatten()
+        {
+            *pin = transpose(pin);
+        }
+        for pin in self
+            .gate_pins
+            .iter_mut()
+            .chain(self.dummy_pins.iter_mut())
+            .chain(self.nets.iter_mut().flat_map(|n| n.values_mut()).flatten())
+        {
+            *pin = transpose(pin);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -345,9 +719,284 @@ pub enum MosError {
     BadParams(String),
     #[error("no devices to draw")]
     NoDevices,
+    #[error("gate direction not supported by this process: {0:?}")]
+    UnsupportedDirection(Dir),
 
     #[error("error doing layout: {0}")]
     Layout(#[from] LayoutError),
+    #[error("pdk error: {0}")]
+    Pdk(#[from] crate::Error),
 }
 
 pub type MosResult<T> = std::result::Result<T, MosError>;
+
+impl Pdk {
+    /// Straps the source/drain, gate and dummy gate pins of `ptx` from metal 0 up to metal `to`,
+    /// moving its pins and ports to that metal.
+    pub(crate) fn strap_transistors(
+        &self,
+        ptx: &mut LayoutTransistors,
+        to: LayerIdx,
+    ) -> MosResult<()> {
+        if to == 0 {
+            return Ok(());
+        }
+        let metal = self.try_metal(to)?;
+
+        let mut insts = Vec::new();
+        let mut straps = HashMap::new();
+        let mut pads = Vec::new();
+        for (j, pins) in ptx.sd_pins.iter_mut().enumerate() {
+            for (i, pin) in pins.iter_mut() {
+                if let Some(pin) = pin {
+                    let net = format!("sd_{}_{}", j, i);
+                    let mut rects = Vec::new();
+                    *pin = self.strap_pin(*pin, to, &mut insts, &mut rects)?;
+                    straps.insert(net.clone(), *pin);
+                    pads.push((net, rects));
+                }
+            }
+        }
+        for (i, pin) in ptx.gate_pins.iter_mut().enumerate() {
+            let net = format!("gate_{}", i);
+            let mut rects = Vec::new();
+            *pin = self.strap_pin(*pin, to, &mut insts, &mut rects)?;
+            straps.insert(net.clone(), *pin);
+            pads.push((net, rects));
+        }
+        for (k, pin) in ptx.dummy_pins.iter_mut().enumerate() {
+            let net = format!("dummy_{}", k);
+            let mut rects = Vec::new();
+            *pin = self.strap_pin(*pin, to, &mut insts, &mut rects)?;
+            straps.insert(net.clone(), *pin);
+            pads.push((net, rects));
+        }
+        self.check_strap_space(&pads)?;
+
+        let mut cell = ptx.cell.write().unwrap();
+        cell.layout.as_mut().unwrap().insts.extend(insts);
+        for port in cell.abs.as_mut().unwrap().ports.iter_mut() {
+            if let Some(pin) = straps.get(&port.net) {
+                port.shapes = HashMap::from([(metal, vec![Shape::Rect(*pin)])]);
+            }
+        }
+        drop(cell);
+
+        ptx.sd_metal = metal;
+        ptx.gate_metal = metal;
+        Ok(())
+    }
+
+    /// Shorts together the source/drain regions of each device that are on the same net,
+    /// and replaces their ports with one port per net of each device.
+    ///
+    /// The straps run perpendicular to the gates on the metal above `ptx.sd_metal`,
+    /// each net in its own lane across the device. Regions of different devices
+    /// on the same net are not shorted, and get separate ports named `{net}_{j}`
+    /// for device `j`.
+    pub(crate) fn connect_sd_nets(
+        &self,
+        ptx: &mut LayoutTransistors,
+        params: &MosParams,
+    ) -> MosResult<()> {
+        let pin_metal = params.strap.unwrap_or(0);
+        let sd_metal = ptx.sd_metal;
+        let grid = self.grid();
+
+        // Straps are laid out with horizontal gates, as the generators draw them,
+        // and reflected across `y = x` for vertical gates.
+        let vert = params.direction == Dir::Vert;
+        let canon = |r: &Rect| if vert { transpose(r) } else { *r };
+
+        let mut insts = Vec::new();
+        let mut elems = Vec::new();
+        let mut ports = Vec::new();
+        let mut replaced = HashSet::new();
+
+        for (j, d) in params.devices.iter().enumerate() {
+            let nets = match params.sd_nets.get(&j) {
+                Some(nets) => nets,
+                None => continue,
+            };
+
+            let mut groups: Vec<(&str, Vec<Rect>)> = Vec::new();
+            for i in 0..=d.fingers {
+                replaced.insert(format!("sd_{}_{}", j, i));
+                let pin = match ptx.sd_pin(j as Uint, i) {
+                    Some(pin) => pin,
+                    None => continue,
+                };
+                let net = nets.net(i as usize).unwrap();
+                match groups.iter_mut().find(|(n, _)| *n == net) {
+                    Some((_, pins)) => pins.push(pin),
+                    None => groups.push((net, vec![pin])),
+                }
+            }
+
+            let lanes = groups.iter().filter(|(_, pins)| pins.len() > 1).count() as Int;
+            let mut straps = HashMap::new();
+            if lanes > 0 {
+                let stack = self.try_stack_name(pin_metal)?;
+                let strap_metal = self.try_metal(pin_metal + 1)?;
+                let ct = self.try_get_contact(
+                    &ContactParams::builder()
+                        .stack(stack)
+                        .rows(1)
+                        .cols(1)
+                        .dir(Dir::Vert)
+                        .build()
+                        .unwrap(),
+                )?;
+                let lane = std::cmp::max(ct.bot_rect().width(), ct.top_rect().width());
+                let space = {
+                    let tc = self.config.read().unwrap();
+                    [sd_metal, strap_metal]
+                        .iter()
+                        .map(|l| tc.try_layer(&self.layer_name(*l)).map(|l| l.space))
+                        .collect::<PdkResult<Vec<_>>>()?
+                        .into_iter()
+                        .max()
+                        .unwrap()
+                };
+
+                let span = canon(&groups[0].1[0]);
+                let total = lanes * lane + (lanes - 1) * space;
+                if total > span.width() {
+                    return Err(MosError::BadParams(format!(
+                        "device {} is too narrow to strap {} source/drain nets",
+                        j, lanes
+                    )));
+                }
+                let mut x = span.p0.x + round_down((span.width() - total) / 2, grid);
+
+                for (net, pins) in groups.iter().filter(|(_, pins)| pins.len() > 1) {
+                    let mut strap: Option<Rect> = None;
+                    for pin in pins.iter().map(canon) {
+                        let within =
+                            Rect::new(Point::new(x, pin.p0.y), Point::new(x + lane, pin.p1.y));
+                        let placed = ct.place_aligned(within, ct.bot, Alignment::CENTER);
+                        let top = placed.top_rect();
+                        strap = Some(match strap {
+                            Some(r) => rect_from_bbox(&r.union(&top.bbox())),
+                            None => top,
+                        });
+                        let mut inst = placed.inst;
+                        if vert {
+                            transpose_inst(&mut inst)?;
+                        }
+                        insts.push(inst);
+                    }
+                    let strap = canon(&strap.unwrap());
+                    elems.push(Element {
+                        net: None,
+                        layer: strap_metal,
+                        purpose: LayerPurpose::Drawing,
+                        inner: Shape::Rect(strap),
+                    });
+                    straps.insert(*net, (strap_metal, strap));
+                    x += lane + space;
+                }
+            }
+
+            for (net, pins) in groups {
+                let mut port = AbstractPort::new(format!("{}_{}", net, j));
+                for pin in pins.iter() {
+                    port.add_shape(sd_metal, Shape::Rect(*pin));
+                }
+                if let Some((layer, strap)) = straps.get(net) {
+                    port.add_shape(*layer, Shape::Rect(*strap));
+                }
+                ports.push(port);
+                ptx.nets[j].insert(net.to_string(), pins);
+            }
+        }
+
+        let mut cell = ptx.cell.write().unwrap();
+        let abs = cell.abs.as_mut().unwrap();
+        abs.ports.retain(|p| !replaced.contains(&p.net));
+        if let Some(port) = ports
+            .iter()
+            .find(|port| abs.ports.iter().any(|p| p.net == port.net))
+        {
+            return Err(MosError::BadParams(format!(
+                "source/drain net port {} conflicts with an existing port",
+                port.net
+            )));
+        }
+        abs.ports.extend(ports);
+        let layout = cell.layout.as_mut().unwrap();
+        layout.insts.extend(insts);
+        layout.elems.extend(elems);
+
+        Ok(())
+    }
+
+    /// Stacks contacts centered on `pin`, which is on metal 0, up to metal `to`.
+    ///
+    /// Each level uses as many cuts as fit along the longer side of the rect below it.
+    /// Landing pads may grow past that side to meet the minimum area rule,
+    /// so every metal rect of the stack, `pin` included, is added to `pads`.
+    /// Returns the rect on metal `to`.
+    fn strap_pin(
+        &self,
+        mut pin: Rect,
+        to: LayerIdx,
+        insts: &mut Vec<Instance>,
+        pads: &mut Vec<(LayerKey, Rect)>,
+    ) -> PdkResult<Rect> {
+        pads.push((self.try_metal(0)?, pin));
+        for i in 0..to {
+            let stack = self.try_stack_name(i)?;
+            let bot = self.try_metal(i)?;
+            let dir = if pin.width() >= pin.height() {
+                Dir::Horiz
+            } else {
+                Dir::Vert
+            };
+            let len = pin.span(dir).length();
+            let ct = match self.contact_sized(stack, dir, dir, bot, len)? {
+                Some(ct) => ct,
+                None => self.try_get_contact(
+                    &ContactParams::builder()
+                        .stack(stack)
+                        .rows(1)
+                        .cols(1)
+                        .dir(dir)
+                        .build()
+                        .unwrap(),
+                )?,
+            };
+            let center = Point::new((pin.p0.x + pin.p1.x) / 2, (pin.p0.y + pin.p1.y) / 2);
+            let placed = ct.place_centered(center, bot);
+            pin = placed.top_rect();
+            pads.push((bot, placed.bboxes[&bot]));
+            pads.push((placed.top, pin));
+            insts.push(placed.inst);
+        }
+        Ok(pin)
+    }
+
+    /// Checks that the metal rects stacked on different pins by [`Pdk::strap_pin`]
+    /// keep the spacing of their layer.
+    fn check_strap_space(&self, pads: &[(String, Vec<(LayerKey, Rect)>)]) -> MosResult<()> {
+        let tc = self.config.read().unwrap();
+        for (i, (a, a_rects)) in pads.iter().enumerate() {
+            for (b, b_rects) in &pads[i + 1..] {
+                for (layer, ra) in a_rects {
+                    for rb in b_rects.iter().filter(|(l, _)| l == layer).map(|(_, r)| r) {
+                        let name = self.layer_name(*layer);
+                        let space = tc.try_layer(&name)?.space;
+                        let (dx, dy) = gap(ra, rb);
+                        if dx * dx + dy * dy < space * space {
+                            return Err(MosError::BadParams(format!(
+                                "{} straps for {} and {} are closer than the minimum spacing of {}",
+                                name, a, b, space
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use layout21::raw::Dir;
+use layout21::raw::{
+    Abstract, AbstractPort, BoundBoxTrait, Cell, Element, LayerPurpose, Layout, LayoutResult,
+    Point, Rect, Shape,
+};
+use layout21::utils::Ptr;
+
+use crate::config::{Int, TechConfig, Uint};
+use crate::contact::{Align, Alignment, ContactParams};
+use crate::geometry::expand_box;
+use crate::mos::{GateSide, Intent, LayoutTransistors, MosError, MosParams, MosResult, MosType};
+use crate::tech::Technology;
+use crate::{Error, LayerIdx, Pdk, PdkLib, PdkResult, Ref};
+
+#[cfg(test)]
+mod tests;
+
+const GF180_DRC_CONFIG_DATA: &str = include_str!("drc_config.yaml");
+
+pub fn tech_config() -> TechConfig {
+    TechConfig::from_yaml(GF180_DRC_CONFIG_DATA).expect("failed to load gf180mcu tech config")
+}
+
+pub fn pdk() -> LayoutResult<Pdk> {
+    Pdk::new(arcstr::literal!("gf180"), tech_config())
+}
+
+/// Creates a new [`PdkLib`] with a cell library of the given `name`.
+pub fn pdk_lib(name: impl Into<String>) -> LayoutResult<PdkLib> {
+    Ok(pdk()?.create_pdk_lib(name))
+}
+
+impl Pdk {
+    /// Draws 3.3V GF180MCU transistors.
+    ///
+    /// Deep n-well, threshold voltage variants and dummy fingers are not supported.
+    pub(crate) fn draw_gf180_mos(&self, params: MosParams) -> MosResult<Ref<LayoutTransistors>> {
+        params.validate()?;
+
+        if params.dnw {
+            return Err(MosError::BadParams(
+                "gf180 does not support deep n-well transistors".to_string(),
+            ));
+        }
+        if params.dummies > 0 {
+            return Err(MosError::BadParams(
+                "gf180 does not support dummy fingers".to_string(),
+            ));
+        }
+        let tech = self.technology()?;
+        for d in params.devices.iter() {
+            tech.vt_implant(d.mos_type, &d.intent)?;
+        }
+
+        let name = params.name();
+
+        let tc = self.config.read().unwrap();
+
+        let poly = self.try_get_layerkey("poly")?;
+        let diff = self.try_get_layerkey("diff")?;
+        let nwell = self.try_get_layerkey("nwell")?;
+        let m1 = self.try_get_layerkey("m1")?;
+
+        let gate_metal = m1;
+        let sd_metal = m1;
+
+        let mut abs = Abstract::new(&name);
+
+        let mut elems = Vec::new();
+        let mut insts = Vec::new();
+
+        let nf = params.fingers();
+        assert!(nf > 0);
+
+        if let Some(dir) = tc.gate_dir {
+            if dir != params.direction {
+                return Err(MosError::UnsupportedDirection(params.direction));
+            }
+        }
+
+        let length = params.length();
+        let gate_ctp = ContactParams::builder()
+            .rows(1)
+            .cols(1)
+            .dir(Dir::Vert)
+            .stack("polyc")
+            .build()
+            .unwrap();
+        let gate_ct = self.try_get_contact(&gate_ctp)?;
+        let gate_metal_bbox = gate_ct.bboxes.get(&m1).unwrap();
+
+        let sd_cts = params
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(j, d)| {
+                let stack = tech.diff_stack(d.mos_type);
+                self.contact_sized(stack, Dir::Horiz, Dir::Vert, diff, d.width)?
+                    .ok_or_else(|| {
+                        MosError::BadParams(format!(
+                            "device {} is too narrow to fit a {} contact",
+                            j, stack
+                        ))
+                    })
+            })
+            .collect::<MosResult<Vec<_>>>()?;
+
+        // Landing pads grow along the gates to meet the minimum area rule,
+        // so adjacent fingers must leave room for them.
+        let pad_height = sd_cts
+            .iter()
+            .map(|ct| ct.bboxes[&m1].height())
+            .chain(std::iter::once(gate_metal_bbox.height()))
+            .max()
+            .unwrap();
+        let finger_space = std::cmp::max(
+            finger_space(&tc, length)?,
+            pad_height + tc.try_layer("m1")?.space - length,
+        );
+
+        // Every source/drain contact is centered between its neighboring gates,
+        // so the outermost gates must leave room for a full finger space.
+        let max_sd_height = sd_cts
+            .iter()
+            .map(|ct| ct.bboxes[&diff].height())
+            .max()
+            .unwrap();
+        let gate_ext = std::cmp::max(
+            diff_edge_to_gate(&tc)?,
+            finger_space / 2 + max_sd_height / 2,
+        );
+        let gate_ext = (gate_ext + tc.grid - 1) / tc.grid * tc.grid;
+
+        // Diff length perpendicular to gates
+        let diff_perp = 2 * gate_ext + nf * length + (nf - 1) * finger_space;
+
+        let mut prev = None;
+        let x0 = 0;
+        let mut cx = x0;
+        let y0 = 0;
+
+        // The line across a diffusion starting at `x` on which the
+        // source/drain contact in region `i` is centered.
+        let sd_line = |x: Int, width: Int, i: Int| {
+            let y = y0 + gate_ext + i * (length + finger_space) - finger_space / 2;
+            Rect::new(Point::new(x, y), Point::new(x + width, y))
+        };
+
+        let mut diff_xs = Vec::new();
+
+        let mut prev_implant: Option<Rect> = None;
+        let mut prev_nwell: Option<Rect> = None;
+
+        for (j, d) in params.devices.iter().enumerate() {
+            if let Some(mt) = prev {
+                cx += if mt != d.mos_type {
+                    diff_to_opposite_diff(&tc)?
+                } else {
+                    tc.try_layer("diff")?.space
+                };
+            }
+
+            diff_xs.push(cx);
+
+            let rect = Rect::new(Point::new(cx, y0), Point::new(cx + d.width, y0 + diff_perp));
+            let implant_name = tech.sd_implant(d.mos_type);
+            let implant = self.try_get_layerkey(implant_name)?;
+
+            // Tap strips are placed below and above the diffusion region.
+            let mut tap_rects = Vec::new();
+            if params.taps {
+                let stack = tech.tap_stack(d.mos_type);
+                let port_name = match d.mos_type {
+                    MosType::Nmos => format!("vnb_tap_{}", j),
+                    MosType::Pmos => format!("vpb_tap_{}", j),
+                };
+                // Taps are implanted like the source/drain of the opposite device type.
+                let tap_implant_name = tech.sd_implant(match d.mos_type {
+                    MosType::Nmos => MosType::Pmos,
+                    MosType::Pmos => MosType::Nmos,
+                });
+                let tap_implant = self.try_get_layerkey(tap_implant_name)?;
+                let ct = self
+                    .contact_sized(stack, Dir::Horiz, Dir::Vert, diff, d.width)?
+                    .ok_or_else(|| {
+                        MosError::BadParams(format!(
+                            "device {} is too narrow to fit a {} contact",
+                            j, stack
+                        ))
+                    })?;
+                let bbox = ct.bboxes.get(&diff).unwrap();
+                let m1_bbox = ct.bboxes.get(&m1).unwrap();
+                // The tap is tall enough to contain its landing pad and meet the
+                // minimum area rule, and far enough from the transistor that the
+                // pad clears the landing pads of the outermost source/drain contacts.
+                let height = std::cmp::max(bbox.height(), m1_bbox.height());
+                let height = self
+                    .try_legalize_area(
+                        diff,
+                        Rect::new(Point::zero(), Point::new(d.width, height)),
+                        Dir::Vert,
+                    )?
+                    .height();
+                let sd_bot = sd_cts[j]
+                    .place_aligned(sd_line(cx, d.width, 0), diff, Alignment::CENTER)
+                    .bboxes[&m1];
+                let sd_overhang = y0 - sd_bot.p0.y;
+                let space = std::cmp::max(tap_space(&tc)?, sd_overhang + tc.try_layer("m1")?.space);
+                let space = (space + tc.grid - 1) / tc.grid * tc.grid;
+
+                let mut port = AbstractPort::new(port_name);
+                for y in [y0 - space - height, y0 + diff_perp + space] {
+                    let tap_rect =
+                        Rect::new(Point::new(cx, y), Point::new(cx + d.width, y + height));
+                    let placed = ct.place_aligned(tap_rect, diff, Alignment::CENTER);
+
+                    let mut implant_box = tap_rect;
+                    expand_box(
+                        &mut implant_box,
+                        tc.try_layer("diff")?.enclosure(tap_implant_name),
+                    );
+                    for (layer, r) in [(diff, tap_rect), (tap_implant, implant_box)] {
+                        elems.push(Element {
+                            net: None,
+                            layer,
+                            purpose: LayerPurpose::Drawing,
+                            inner: Shape::Rect(r),
+                        });
+                    }
+
+                    port.add_shape(sd_metal, Shape::Rect(placed.bboxes[&m1]));
+                    insts.push(placed.inst);
+                    tap_rects.push(tap_rect);
+                }
+                abs.add_port(port);
+            }
+
+            let mut implant_box = rect;
+            expand_box(
+                &mut implant_box,
+                tc.try_layer("diff")?.enclosure(implant_name),
+            );
+
+            let mut port = AbstractPort::new(format!("{}_{}", implant_name, j));
+            port.add_shape(implant, Shape::Rect(implant_box));
+            abs.add_port(port);
+
+            if let (Some(prev_implant), Some(mt)) = (prev_implant, prev) {
+                if mt == d.mos_type {
+                    implant_box = implant_box.union(&prev_implant.into()).into_rect();
+                }
+            }
+
+            elems.push(Element {
+                net: None,
+                layer: implant,
+                purpose: LayerPurpose::Drawing,
+                inner: Shape::Rect(implant_box),
+            });
+            prev_implant = Some(implant_box);
+
+            if d.mos_type == MosType::Pmos {
+                let mut well_box = rect;
+                expand_box(&mut well_box, tc.try_layer("diff")?.enclosure("nwell"));
+                for tap_rect in tap_rects.iter() {
+                    let mut tap_well = *tap_rect;
+                    expand_box(&mut tap_well, tc.try_layer("diff")?.enclosure("nwell"));
+                    well_box = well_box.union(&tap_well.into()).into_rect();
+                }
+
+                let mut port = AbstractPort::new(format!("vpb_{}", j));
+                port.add_shape(nwell, Shape::Rect(well_box));
+                abs.add_port(port);
+
+                if let Some(prev_nwell) = prev_nwell {
+                    well_box = well_box.union(&prev_nwell.into()).into_rect();
+                }
+
+                elems.push(Element {
+                    net: None,
+                    layer: nwell,
+                    purpose: LayerPurpose::Drawing,
+                    inner: Shape::Rect(well_box),
+                });
+
+                prev_nwell = Some(well_box);
+            } else {
+                prev_nwell = None;
+            }
+
+            elems.push(Element {
+                net: None,
+                layer: diff,
+                purpose: LayerPurpose::Drawing,
+                inner: Shape::Rect(rect),
+            });
+
+            cx += d.width;
+
+            prev = Some(d.mos_type);
+        }
+
+        let mut gate_pins = Vec::with_capacity(nf as usize);
+
+        let sides = params.contact_strategy.sides(nf as usize)?;
+
+        let xpoly = x0 - tc.try_layer("poly")?.extension("diff");
+        let mut ypoly = y0 + gate_ext;
+        let wpoly = cx - xpoly + tc.try_layer("poly")?.extension("diff");
+
+        // Each gate contact is centered on its finger, abutting the end of the poly.
+        // `finger_space` leaves enough room between fingers for the contact landing pads.
+        for (i, side) in sides.into_iter().enumerate() {
+            let rect = Rect {
+                p0: Point::new(xpoly, ypoly),
+                p1: Point::new(xpoly + wpoly, ypoly + length),
+            };
+            elems.push(Element {
+                net: None,
+                layer: poly,
+                purpose: LayerPurpose::Drawing,
+                inner: Shape::Rect(rect),
+            });
+
+            let (x, horiz) = match side {
+                GateSide::Left => (rect.p0.x, Align::End),
+                GateSide::Right => (rect.p1.x, Align::Start),
+            };
+            let edge = Rect::new(Point::new(x, rect.p0.y), Point::new(x, rect.p1.y));
+            let placed = gate_ct.place_aligned(edge, poly, Alignment::new(horiz, Align::Center));
+
+            let ct_box = placed.bboxes[&m1];
+
+            let mut port = AbstractPort::new(format!("gate_{}", i));
+            port.add_shape(gate_metal, Shape::Rect(ct_box));
+            abs.add_port(port);
+            gate_pins.push(ct_box);
+            insts.push(placed.inst);
+
+            ypoly += length + finger_space;
+        }
+
+        // Add source/drain contacts
+
+        let mut sd_pins = (0..params.devices.len())
+            .map(|_| HashMap::new())
+            .collect::<Vec<_>>();
+
+        for i in 0..=nf {
+            for (d, (j, x)) in params.devices.iter().zip(diff_xs.iter().enumerate()) {
+                if d.skip_sd_metal.contains(&(i as usize)) {
+                    continue;
+                }
+                let placed =
+                    sd_cts[j].place_aligned(sd_line(*x, d.width, i), diff, Alignment::CENTER);
+                let sd_rect = placed.bboxes[&m1];
+                insts.push(placed.inst);
+
+                let mut port = AbstractPort::new(format!("sd_{}_{}", j, i));
+                port.add_shape(sd_metal, Shape::Rect(sd_rect));
+                abs.add_port(port);
+                sd_pins[j].insert(i as Uint, Some(sd_rect));
+            }
+        }
+
+        let layout = Layout {
+            name: name.clone(),
+            insts,
+            annotations: vec![],
+            elems,
+        };
+
+        let cell = Cell {
+            name,
+            abs: Some(abs),
+            layout: Some(layout),
+        };
+
+        let mut transistors = LayoutTransistors {
+            cell: Ptr::new(cell),
+            sd_metal,
+            gate_metal,
+            sd_pins,
+            gate_pins,
+            dummy_pins: Vec::new(),
+            num_fingers: params.devices[0].fingers as usize,
+            num_devices: params.devices.len(),
+            nets: vec![HashMap::new(); params.devices.len()],
+        };
+        // The layout above is drawn with horizontal gates;
+        // vertical gates are obtained by reflecting across the line `y = x`.
+        if params.direction == Dir::Vert {
+            transistors.transpose()?;
+        }
+        if let Some(metal) = params.strap {
+            self.strap_transistors(&mut transistors, metal)?;
+        }
+        self.connect_sd_nets(&mut transistors, &params)?;
+
+        Ok(Arc::new(transistors))
+    }
+}
+
+/// The GlobalFoundries 180nm MCU process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gf180;
+
+impl Technology for Gf180 {
+    fn name(&self) -> &str {
+        "gf180"
+    }
+
+    fn draw_mos(&self, pdk: &Pdk, params: MosParams) -> MosResult<Ref<LayoutTransistors>> {
+        pdk.draw_gf180_mos(params)
+    }
+
+    fn metal_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        Ok(match i {
+            0 => "m1",
+            1 => "m2",
+            2 => "m3",
+            3 => "m4",
+            4 => "m5",
+            _ => return Err(Error::UnknownLayer(format!("metal {}", i))),
+        })
+    }
+
+    fn via_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        Ok(match i {
+            0 => "via1",
+            1 => "via2",
+            2 => "via3",
+            3 => "via4",
+            _ => return Err(Error::UnknownLayer(format!("via {}", i))),
+        })
+    }
+
+    fn stack_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        Ok(match i {
+            0 => "via1",
+            1 => "via2",
+            2 => "via3",
+            3 => "via4",
+            _ => return Err(Error::UnknownStack(format!("above metal {}", i))),
+        })
+    }
+
+    fn diff_stack(&self, mos_type: MosType) -> &'static str {
+        match mos_type {
+            MosType::Nmos => "ndiffc",
+            MosType::Pmos => "pdiffc",
+        }
+    }
+
+    fn tap_stack(&self, mos_type: MosType) -> &'static str {
+        match mos_type {
+            MosType::Nmos => "ptap",
+            MosType::Pmos => "ntap",
+        }
+    }
+
+    fn sd_implant(&self, mos_type: MosType) -> &'static str {
+        match mos_type {
+            MosType::Nmos => "nplus",
+            MosType::Pmos => "pplus",
+        }
+    }
+
+    fn vt_implant(&self, mos_type: MosType, intent: &Intent) -> MosResult<Option<&'static str>> {
+        match intent {
+            Intent::Svt => Ok(None),
+            intent => Err(MosError::BadParams(format!(
+                "gf180 does not support {} devices with intent {}",
+                mos_type, intent
+            ))),
+        }
+    }
+}
+
+/// The spacing between adjacent gates.
+///
+/// Leaves room for a source/drain contact between the gates,
+/// and for the landing pads of the gate contacts at either end.
+pub fn finger_space(tc: &TechConfig, length: Int) -> PdkResult<Int> {
+    let pad = tc.try_layer("contact")?.width + 2 * tc.try_layer("contact")?.enclosure("poly");
+    Ok([
+        2 * tc.space("gate", "contact") + tc.try_layer("contact")?.width,
+        tc.try_layer("poly")?.space,
+        pad + tc.try_layer("poly")?.space - length,
+    ]
+    .into_iter()
+    .max()
+    .unwrap())
+}
+
+pub fn diff_edge_to_gate(tc: &TechConfig) -> PdkResult<Int> {
+    Ok([
+        tc.try_layer("diff")?.extension("poly"),
+        tc.space("gate", "contact")
+            + tc.try_layer("contact")?.width
+            + tc.try_layer("contact")?.enclosure("diff"),
+    ]
+    .into_iter()
+    .max()
+    .unwrap())
+}
+
+pub fn diff_to_opposite_diff(tc: &TechConfig) -> PdkResult<Int> {
+    Ok(tc.space("diff", "nwell") + tc.try_layer("diff")?.enclosure("nwell"))
+}
+
+/// The spacing between a transistor's diffusion region and its tap strips.
+///
+/// Keeps the source/drain implant and the tap implant from overlapping.
+pub fn tap_space(tc: &TechConfig) -> PdkResult<Int> {
+    let diff = tc.try_layer("diff")?;
+    Ok(std::cmp::max(
+        diff.space,
+        diff.enclosure("nplus") + diff.enclosure("pplus"),
+    ))
+}
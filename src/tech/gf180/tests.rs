@@ -0,0 +1,587 @@
+use std::path::{Path, PathBuf};
+
+use layout21::raw::geom::Dir;
+use layout21::raw::{BoundBoxTrait, Point, Rect};
+use layout21::{
+    raw::{DepOrder, Library},
+    utils::{Ptr, PtrList},
+};
+
+use crate::drc::assert_drc_clean;
+use crate::geometry::transpose;
+use crate::{
+    contact::ContactParams,
+    mos::{GateContactStrategy, Intent, MosDevice, MosError, MosParams, MosType, SdNets},
+};
+use crate::{Error, Pdk};
+
+fn nand2_params() -> MosParams {
+    let mut params = MosParams::new();
+    params
+        .dnw(false)
+        .direction(Dir::Horiz)
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(280)
+                .fingers(2)
+                .skip_sd_metal(vec![1])
+                .build()
+                .unwrap(),
+        )
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Pmos)
+                .width(2_000)
+                .length(280)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        );
+    params
+}
+
+#[test]
+fn test_draw_gf180_mos_nand2() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let ptx = pdk.draw_gf180_mos(nand2_params())?;
+    assert_eq!(ptx.sd_metal, pdk.metal(0));
+    assert_eq!(ptx.gate_pins.len(), 2);
+    assert!(ptx.sd_pin(0, 1).is_none());
+    assert!(ptx.vpb_port(1).is_some());
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    let mut lib = Library::new("test_draw_gf180_mos_nand2", pdk.units());
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&ptx.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_gf180_mos_nand2.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_pdk_lib() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let mut lib = super::pdk_lib("test_draw_gf180_mos_pdk_lib")?;
+    lib.draw_mos(nand2_params())?;
+    lib.save_gds(output("test_draw_gf180_mos_pdk_lib.gds"))?;
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_min_size() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for mos_type in [MosType::Nmos, MosType::Pmos] {
+        let mut params = MosParams::new();
+        params.taps(true).add_device(
+            MosDevice::builder()
+                .mos_type(mos_type)
+                .width(360)
+                .length(280)
+                .build()
+                .unwrap(),
+        );
+        let ptx = pdk.draw_gf180_mos(params)?;
+        assert_drc_clean(&pdk, &ptx.cell);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_unsupported() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for (mos_type, intent) in [
+        (MosType::Nmos, Intent::Lvt),
+        (MosType::Pmos, Intent::Hvt),
+        (MosType::Nmos, Intent::Custom("6v0".to_string())),
+    ] {
+        let mut params = MosParams::new();
+        params.add_device(
+            MosDevice::builder()
+                .mos_type(mos_type)
+                .width(1_000)
+                .length(280)
+                .intent(intent)
+                .build()
+                .unwrap(),
+        );
+        assert!(matches!(
+            pdk.draw_gf180_mos(params),
+            Err(MosError::BadParams(_))
+        ));
+    }
+
+    let mut params = nand2_params();
+    params.dnw(true);
+    assert!(matches!(
+        pdk.draw_gf180_mos(params),
+        Err(MosError::BadParams(_))
+    ));
+
+    let mut params = nand2_params();
+    params.dummies(1);
+    assert!(matches!(
+        pdk.draw_gf180_mos(params),
+        Err(MosError::BadParams(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_gate_contact_strategies() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let mut lib = Library::new("test_draw_gf180_mos_gate_contact_strategies", pdk.units());
+    lib.layers = pdk.layers();
+
+    for (strategy, left) in [
+        (
+            GateContactStrategy::SingleSide,
+            [true, true, true, true, true, true],
+        ),
+        (
+            GateContactStrategy::Alternate,
+            [true, false, true, false, true, false],
+        ),
+        (
+            GateContactStrategy::Abba,
+            [true, false, false, true, true, false],
+        ),
+    ] {
+        let mut params = MosParams::new();
+        params
+            .direction(Dir::Horiz)
+            .contact_strategy(strategy)
+            .add_device(
+                MosDevice::builder()
+                    .mos_type(MosType::Nmos)
+                    .width(1_000)
+                    .length(280)
+                    .fingers(6)
+                    .build()
+                    .unwrap(),
+            );
+
+        let ptx = pdk.draw_gf180_mos(params)?;
+        assert_eq!(ptx.gate_pins.len(), 6);
+        for (pin, left) in ptx.gate_pins.iter().zip(left) {
+            if left {
+                assert!(pin.p1.x < 0);
+            } else {
+                assert!(pin.p0.x > 1_000);
+            }
+        }
+        assert_drc_clean(&pdk, &ptx.cell);
+        lib.cells.push(Ptr::clone(&ptx.cell));
+    }
+
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_gf180_mos_gate_contact_strategies.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_vertical() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let mut params = nand2_params();
+    params.contact_strategy(GateContactStrategy::Alternate);
+
+    let horiz = pdk.draw_gf180_mos(params.clone())?;
+    params.direction(Dir::Vert);
+    let vert = pdk.draw_gf180_mos(params)?;
+
+    for (h, v) in horiz.gate_pins.iter().zip(vert.gate_pins.iter()) {
+        assert_eq!(transpose(h), *v);
+    }
+    for i in 0..2 {
+        for j in 0..=2 {
+            assert_eq!(horiz.sd_pin(i, j).map(|r| transpose(&r)), vert.sd_pin(i, j));
+        }
+    }
+
+    assert_drc_clean(&pdk, &horiz.cell);
+    assert_drc_clean(&pdk, &vert.cell);
+
+    let mut lib = Library::new("test_draw_gf180_mos_vertical", pdk.units());
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&horiz.cell));
+    lib.cells.push(Ptr::clone(&vert.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_gf180_mos_vertical.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_fixed_gate_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let mut tc = super::tech_config();
+    tc.gate_dir = Some(Dir::Horiz);
+    let pdk = Pdk::new(arcstr::literal!("gf180"), tc)?;
+
+    let mut params = nand2_params();
+    params.direction(Dir::Vert);
+    assert!(matches!(
+        pdk.draw_gf180_mos(params.clone()),
+        Err(MosError::UnsupportedDirection(Dir::Vert))
+    ));
+    params.direction(Dir::Horiz);
+    pdk.draw_gf180_mos(params)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_taps() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let mut params = nand2_params();
+    params.taps(true);
+
+    let pdk = super::pdk()?;
+    let ptx = pdk.draw_gf180_mos(params)?;
+
+    let m1 = pdk.metal(0);
+    let nwell = pdk.get_layerkey("nwell").unwrap();
+
+    let vnb = ptx.vnb_tap_port(0).unwrap();
+    assert_eq!(vnb.shapes.get(&m1).unwrap().len(), 2);
+    assert!(ptx.vpb_tap_port(0).is_none());
+
+    let vpb = ptx.vpb_tap_port(1).unwrap();
+    assert_eq!(vpb.shapes.get(&m1).unwrap().len(), 2);
+    assert!(ptx.vnb_tap_port(1).is_none());
+
+    let well = ptx.vpb_port(1).unwrap().largest_rect(nwell)?;
+    for shape in vpb.shapes.get(&m1).unwrap() {
+        let bbox = shape.bbox();
+        assert!(well.p0.x < bbox.p0.x && well.p0.y < bbox.p0.y);
+        assert!(well.p1.x > bbox.p1.x && well.p1.y > bbox.p1.y);
+    }
+
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    let mut lib = Library::new("test_draw_gf180_mos_taps", pdk.units());
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&ptx.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_gf180_mos_taps.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_finger_pitch() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let mut params = MosParams::new();
+    params
+        .dnw(false)
+        .direction(Dir::Horiz)
+        .taps(true)
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(280)
+                .fingers(3)
+                .build()
+                .unwrap(),
+        );
+    let ptx = pdk.draw_gf180_mos(params)?;
+
+    let poly = pdk.get_layerkey("poly").unwrap();
+    let diff = pdk.get_layerkey("diff").unwrap();
+    let m1 = pdk.metal(0);
+
+    let cell = ptx.cell.read().unwrap();
+    let rects = |layer| {
+        let mut rects = cell
+            .layout
+            .as_ref()
+            .unwrap()
+            .elems
+            .iter()
+            .filter(|e| e.layer == layer)
+            .map(|e| e.inner.bbox().into_rect())
+            .collect::<Vec<_>>();
+        rects.sort_by_key(|r| r.p0.y);
+        rects
+    };
+
+    // Fingers are spaced far enough apart for the gate and source/drain
+    // landing pads, which are taller than the contacts themselves.
+    let gates = rects(poly);
+    assert_eq!(gates.len(), 3);
+    let pitches = gates
+        .windows(2)
+        .map(|w| w[1].p0.y - w[0].p0.y)
+        .collect::<Vec<_>>();
+    assert_eq!(pitches, vec![860; 2]);
+    {
+        let tc = pdk.config.read().unwrap();
+        assert!(860 >= 280 + super::finger_space(&tc, 280)?);
+    }
+    let m1_space = pdk.config.read().unwrap().layer("m1").space;
+    for w in ptx.gate_pins.windows(2) {
+        assert!(w[1].p0.y - w[0].p1.y >= m1_space);
+    }
+
+    // Taps sit below and above the diffusion region, far enough away
+    // that their landing pads clear those of the outermost source/drain contacts.
+    let diffs = rects(diff);
+    assert_eq!(diffs.len(), 3);
+    let (tap_bot, body, tap_top) = (diffs[0], diffs[1], diffs[2]);
+    assert_eq!(body.p0.y - tap_bot.p1.y, 320);
+    assert_eq!(tap_top.p0.y - body.p1.y, 320);
+    {
+        let tc = pdk.config.read().unwrap();
+        assert!(320 >= super::tap_space(&tc)?);
+    }
+    let vnb = ptx.vnb_tap_port(0).unwrap();
+    let sd_bot = ptx.sd_pin(0, 0).unwrap();
+    let sd_top = ptx.sd_pin(0, 3).unwrap();
+    for shape in vnb.shapes.get(&m1).unwrap() {
+        let pad = shape.bbox().into_rect();
+        assert!(sd_bot.p0.y - pad.p1.y >= m1_space || pad.p0.y - sd_top.p1.y >= m1_space);
+    }
+
+    drop(cell);
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_strap() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let mut params = nand2_params();
+    params.strap(1);
+    let ptx = pdk.draw_gf180_mos(params)?;
+
+    let m2 = pdk.metal(1);
+    assert_eq!(ptx.sd_metal, m2);
+    assert_eq!(ptx.gate_metal, m2);
+    let pin = ptx.sd_pin(1, 1).unwrap();
+    assert_eq!(ptx.sd_port(1, 1).unwrap().largest_rect(m2).unwrap(), pin);
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_gf180_mos_sd_nets() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for strap in [None, Some(1)] {
+        let mut params = MosParams::new();
+        for (j, (mos_type, width, rail)) in
+            [(MosType::Nmos, 1_400, "vss"), (MosType::Pmos, 2_000, "vdd")]
+                .into_iter()
+                .enumerate()
+        {
+            params
+                .add_device(
+                    MosDevice::builder()
+                        .mos_type(mos_type)
+                        .width(width)
+                        .length(280)
+                        .fingers(4)
+                        .build()
+                        .unwrap(),
+                )
+                .sd_nets(
+                    j,
+                    SdNets::Alternating {
+                        even: rail.to_string(),
+                        odd: "y".to_string(),
+                    },
+                );
+        }
+        params.strap = strap;
+        let ptx = pdk.draw_gf180_mos(params)?;
+
+        assert_eq!(ptx.net_pins(0, "vss").len(), 3);
+        assert_eq!(ptx.net_pins(1, "vdd").len(), 3);
+        let strap_metal = pdk.metal(strap.unwrap_or(0) + 1);
+        for j in 0..2 {
+            assert_eq!(ptx.net_pins(j, "y").len(), 2);
+            assert_eq!(ptx.net_port(j, "y").unwrap().shapes[&strap_metal].len(), 1);
+        }
+        assert_drc_clean(&pdk, &ptx.cell);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_gf180_draw_contact() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let n = 3;
+
+    let pdk = super::pdk()?;
+
+    let mut lib = Library::new("test_gf180_draw_contact", pdk.units());
+    lib.layers = pdk.layers();
+
+    for i in 1..=n {
+        for j in 1..=n {
+            for stack in [
+                "ntap", "ptap", "ndiffc", "pdiffc", "polyc", "via1", "via2", "via3", "via4",
+            ] {
+                for dir in [Dir::Horiz, Dir::Vert] {
+                    let mut cp = ContactParams::builder();
+                    let cp = cp
+                        .stack(stack.to_string())
+                        .rows(i)
+                        .cols(j)
+                        .dir(dir)
+                        .build()
+                        .unwrap();
+                    let ct = pdk.get_contact(&cp);
+                    lib.cells.push(Ptr::clone(&ct.cell));
+                }
+            }
+        }
+    }
+
+    let gds = lib.to_gds()?;
+    gds.save(output("test_gf180_draw_contact.gds"))?;
+    Ok(())
+}
+
+#[test]
+fn test_gf180_metal_stacks() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for i in 0..=3 {
+        let cp = ContactParams::builder()
+            .stack(pdk.stack_name(i).to_string())
+            .rows(2)
+            .cols(2)
+            .dir(Dir::Vert)
+            .build()
+            .unwrap();
+        let ct = pdk.get_contact(&cp);
+
+        let cell = ct.cell.read().unwrap();
+        let elems = &cell.layout.as_ref().unwrap().elems;
+        for layer in [pdk.metal(i), pdk.via(i), pdk.metal(i + 1)] {
+            assert!(elems.iter().any(|e| e.layer == layer));
+        }
+    }
+
+    assert!(matches!(pdk.try_metal(5), Err(Error::UnknownLayer(_))));
+    assert!(matches!(pdk.try_stack_name(4), Err(Error::UnknownStack(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_gf180_via_stack() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let within = Rect::new(Point::zero(), Point::new(1000, 1000));
+    let vs = pdk.via_stack(0, 4, within).expect("via stack should fit");
+
+    assert_eq!(vs.contacts.len(), 4);
+    for i in 0..=4 {
+        let bbox = vs.bboxes[&pdk.metal(i)];
+        assert!(bbox.p0.x >= 0 && bbox.p0.y >= 0);
+        assert!(bbox.p1.x <= within.width() && bbox.p1.y <= within.height());
+    }
+    assert_drc_clean(&pdk, &vs.cell);
+
+    Ok(())
+}
+
+#[test]
+fn test_gf180_contact_sized() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let diff = pdk.get_layerkey("diff").unwrap();
+    let ct = pdk
+        .get_contact_sized("ndiffc", Dir::Vert, diff, 360)
+        .unwrap();
+    assert_eq!(ct.cols, 1);
+    assert_eq!(ct.rows, 1);
+
+    let ct = pdk
+        .get_contact_sized("ndiffc", Dir::Horiz, diff, 830)
+        .unwrap();
+    assert_eq!(ct.cols, 2);
+    assert_eq!(ct.rows, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_gf180_draw_contact_within() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let m2 = pdk.metal(1);
+    let ct = pdk
+        .get_contact_within("via1", m2, Rect::new(Point::zero(), Point::new(1_000, 400)))
+        .unwrap();
+    assert_eq!(ct.rows, 1);
+    assert_eq!(ct.cols, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_gf180_contact_min_area() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for i in 0..4 {
+        let ct = pdk.get_contact(
+            &ContactParams::builder()
+                .stack(pdk.stack_name(i).to_string())
+                .rows(1)
+                .cols(1)
+                .dir(Dir::Vert)
+                .build()
+                .unwrap(),
+        );
+        for metal in [pdk.metal(i), pdk.metal(i + 1)] {
+            let area = pdk
+                .config
+                .read()
+                .unwrap()
+                .layer(&pdk.layer_name(metal))
+                .area;
+            let bbox = ct.bboxes[&metal];
+            assert!(bbox.width() * bbox.height() >= area);
+        }
+        assert_drc_clean(&pdk, &ct.cell);
+    }
+
+    Ok(())
+}
+
+fn output(name: impl AsRef<Path>) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target/_tests/gds/")
+        .join(name)
+}
+
+fn setup() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/_tests/gds/"))?;
+    Ok(())
+}
@@ -1,2 +1,77 @@
+//! Technology backends.
+//!
+//! Each supported process implements [`Technology`], which the [`Pdk`]
+//! dispatches to for everything that cannot be derived from the
+//! [`TechConfig`](crate::config::TechConfig) alone.
+use std::fmt::Debug;
+
+use crate::contact::{Contact, ContactParams};
+use crate::mos::{Intent, LayoutTransistors, MosParams, MosResult, MosType};
+use crate::{LayerIdx, Pdk, PdkResult, Ref};
+
+#[cfg(feature = "gf180")]
+pub mod gf180;
 #[cfg(feature = "sky130")]
 pub mod sky130;
+
+/// A process technology that primitive cells can be generated for.
+pub trait Technology: Debug + Send + Sync {
+    /// The name of the technology, such as `"sky130"`.
+    fn name(&self) -> &str;
+
+    /// Draws a set of transistors with the given parameters.
+    fn draw_mos(&self, pdk: &Pdk, params: MosParams) -> MosResult<Ref<LayoutTransistors>>;
+
+    /// Draws a contact with the given parameters.
+    ///
+    /// By default, contacts are drawn from the stack declared in the
+    /// [`TechConfig`](crate::config::TechConfig).
+    /// Callers should generally use [`Pdk::get_contact`], which caches the result.
+    fn draw_contact(&self, pdk: &Pdk, params: &ContactParams) -> PdkResult<Ref<Contact>> {
+        pdk.draw_stack_contact(params)
+    }
+
+    /// The name of metal layer `i`, where metal 0 is the lowest routing layer.
+    fn metal_name(&self, i: LayerIdx) -> PdkResult<&'static str>;
+
+    /// The name of the via layer connecting metal `i+1` to metal `i`.
+    fn via_name(&self, i: LayerIdx) -> PdkResult<&'static str>;
+
+    /// The name of the stack connecting metal `i+1` to metal `i`.
+    fn stack_name(&self, i: LayerIdx) -> PdkResult<&'static str>;
+
+    /// The name of the stack used to contact diffusion of the given transistor type.
+    fn diff_stack(&self, mos_type: MosType) -> &'static str;
+
+    /// The name of the stack used to tap the well or substrate
+    /// underneath transistors of the given type.
+    fn tap_stack(&self, mos_type: MosType) -> &'static str;
+
+    /// The source/drain implant layer for transistors of the given type.
+    fn sd_implant(&self, mos_type: MosType) -> &'static str;
+
+    /// The threshold voltage implant layer for the given device,
+    /// or [`None`] if the device needs no additional implant.
+    ///
+    /// Returns an error if the process has no such device.
+    fn vt_implant(&self, mos_type: MosType, intent: &Intent) -> MosResult<Option<&'static str>>;
+}
+
+impl PartialEq for dyn Technology {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn Technology {}
+
+/// Looks up one of the technologies built into this crate by name.
+pub fn builtin(name: &str) -> Option<Ref<dyn Technology>> {
+    match name {
+        #[cfg(feature = "gf180")]
+        "gf180" => Some(Ref::new(gf180::Gf180)),
+        #[cfg(feature = "sky130")]
+        "sky130" => Some(Ref::new(sky130::Sky130)),
+        _ => None,
+    }
+}
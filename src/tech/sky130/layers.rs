@@ -14,6 +14,10 @@ pub trait Sky130Pdk {
     fn met2(&self) -> LayerKey;
     fn via2(&self) -> LayerKey;
     fn met3(&self) -> LayerKey;
+    fn via3(&self) -> LayerKey;
+    fn met4(&self) -> LayerKey;
+    fn via4(&self) -> LayerKey;
+    fn met5(&self) -> LayerKey;
 }
 
 impl Sky130Pdk for Pdk {
@@ -50,6 +54,18 @@ impl Sky130Pdk for Pdk {
     fn met3(&self) -> LayerKey {
         self.get_layerkey("m3").unwrap()
     }
+    fn via3(&self) -> LayerKey {
+        self.get_layerkey("via3").unwrap()
+    }
+    fn met4(&self) -> LayerKey {
+        self.get_layerkey("m4").unwrap()
+    }
+    fn via4(&self) -> LayerKey {
+        self.get_layerkey("via4").unwrap()
+    }
+    fn met5(&self) -> LayerKey {
+        self.get_layerkey("m5").unwrap()
+    }
 }
 
 #[cfg(test)]
@@ -70,6 +86,10 @@ mod tests {
         let _ = pdk.met2();
         let _ = pdk.via2();
         let _ = pdk.met3();
+        let _ = pdk.via3();
+        let _ = pdk.met4();
+        let _ = pdk.via4();
+        let _ = pdk.met5();
         Ok(())
     }
 }
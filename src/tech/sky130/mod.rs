@@ -2,26 +2,25 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use layout21::raw::{
-    Abstract, AbstractPort, BoundBoxTrait, Cell, Element, Instance, LayerKey, LayerPurpose, Layout,
-    LayoutResult, Library, Point, Rect, Shape, Units,
+    Abstract, AbstractPort, BoundBox, BoundBoxTrait, Cell, Element, LayerKey, LayerPurpose, Layout,
+    LayoutResult, Point, Rect, Shape,
 };
 use layout21::raw::{Dir, Span};
 use layout21::utils::Ptr;
 
 use crate::config::{Int, Uint};
-use crate::{LayerIdx, PdkLib, Ref};
+use crate::tech::Technology;
+use crate::{Error, LayerIdx, PdkLib, PdkResult, Ref};
 
-use crate::contact::{Contact, ContactParams};
-use crate::geometry::{expand_box, expand_box_min_width, rect_from_bbox, translate};
-use crate::mos::{LayoutTransistors, MosType};
+use crate::contact::{Align, Alignment, Contact, ContactParams};
+use crate::geometry::{expand_box, expand_box_min_width};
+use crate::mos::{GateSide, Intent, LayoutTransistors, MosDevice, MosError, MosType};
 use crate::{
     config::TechConfig,
     mos::{MosParams, MosResult},
     Pdk,
 };
 
-use self::layers::Sky130Pdk;
-
 pub(crate) mod layers;
 #[cfg(test)]
 mod tests;
@@ -38,12 +37,7 @@ pub fn pdk() -> LayoutResult<Pdk> {
 
 /// Creates a new [`PdkLib`] with a cell library of the given `name`.
 pub fn pdk_lib(name: impl Into<String>) -> LayoutResult<PdkLib> {
-    Ok(PdkLib {
-        tech: arcstr::literal!("sky130"),
-        pdk: pdk()?,
-        lib: Library::new(name, Units::Nano),
-        ptx: HashMap::new(),
-    })
+    Ok(pdk()?.create_pdk_lib(name))
 }
 
 impl Pdk {
@@ -52,8 +46,8 @@ impl Pdk {
 
         let name = params.name();
 
-        let gate_metal = self.li1();
-        let sd_metal = self.li1();
+        let gate_metal = self.try_get_layerkey("li")?;
+        let sd_metal = gate_metal;
 
         let mut abs = Abstract::new(&name);
 
@@ -61,17 +55,34 @@ impl Pdk {
         let mut insts = Vec::new();
 
         let tc = self.config.read().unwrap();
-        let layers = self.layers.read().unwrap();
+        let tech = self.technology()?;
 
-        let poly = layers.keyname("poly").unwrap();
-        let diff = layers.keyname("diff").unwrap();
+        let poly = self.try_get_layerkey("poly")?;
+        let diff = self.try_get_layerkey("diff")?;
+        let npc = self.try_get_layerkey("npc")?;
 
         let nf = params.fingers();
         assert!(nf > 0);
 
+        if let Some(dir) = tc.gate_dir {
+            if dir != params.direction {
+                return Err(MosError::UnsupportedDirection(params.direction));
+            }
+        }
+
+        let implants = params
+            .devices
+            .iter()
+            .map(|d| tech.vt_implant(d.mos_type, &d.intent))
+            .collect::<MosResult<Vec<_>>>()?;
+
+        // Dummy fingers are drawn like real fingers on either side of them.
+        let nd = params.dummies;
+        let nt = nf + 2 * nd;
+
         // Diff length perpendicular to gates
         let diff_perp =
-            2 * diff_edge_to_gate(&tc) + nf * params.length() + (nf - 1) * finger_space(&tc);
+            2 * diff_edge_to_gate(&tc)? + nt * params.length() + (nt - 1) * finger_space(&tc)?;
 
         let mut prev = None;
         let x0 = 0;
@@ -82,26 +93,68 @@ impl Pdk {
 
         let mut prev_psdm: Option<Rect> = None;
         let mut prev_nsdm: Option<Rect> = None;
+        let mut prev_nwell: Option<Rect> = None;
+        let mut prev_vt: Option<Rect> = None;
+
+        // The region that must be kept clear of the deep n-well's n-well ring.
+        let mut dnw_hole = BoundBox::empty();
 
         for (j, d) in params.devices.iter().enumerate() {
             if let Some(mt) = prev {
-                if mt != d.mos_type {
-                    cx += diff_to_opposite_diff(&tc);
+                let space = if mt != d.mos_type && params.taps {
+                    tapped_diff_to_opposite_diff(&tc)?
+                } else if mt != d.mos_type {
+                    diff_to_opposite_diff(&tc)?
                 } else {
-                    cx += tc.layer("diff").space;
-                }
+                    tc.try_layer("diff")?.space
+                };
+                cx += std::cmp::max(space, vt_implant_space(&tc, implants[j - 1], implants[j])?);
             }
 
             diff_xs.push(cx);
 
             let rect = Rect::new(Point::new(cx, y0), Point::new(cx + d.width, y0 + diff_perp));
 
+            // Tap strips are placed below and above the diffusion region.
+            let mut tap_rects = Vec::new();
+            if params.taps {
+                let stack = tech.tap_stack(d.mos_type);
+                let port_name = match d.mos_type {
+                    MosType::Nmos => format!("vnb_tap_{}", j),
+                    MosType::Pmos => format!("vpb_tap_{}", j),
+                };
+                let tap = self.try_get_layerkey(stack)?;
+                let ct = self
+                    .try_get_contact_sized(stack, Dir::Horiz, tap, d.width)?
+                    .ok_or_else(|| {
+                        MosError::BadParams(format!(
+                            "device {} is too narrow to fit a {} contact",
+                            j, stack
+                        ))
+                    })?;
+                let space = tc.try_layer("diff")?.space;
+
+                let mut port = AbstractPort::new(port_name);
+                for (y, vert) in [
+                    (y0 - space, Align::End),
+                    (y0 + diff_perp + space, Align::Start),
+                ] {
+                    let row = Rect::new(Point::new(cx, y), Point::new(cx + d.width, y));
+                    let placed = ct.place_aligned(row, tap, Alignment::new(Align::Center, vert));
+                    tap_rects.push(placed.bboxes[&tap]);
+                    port.add_shape(sd_metal, Shape::Rect(placed.bboxes[&sd_metal]));
+                    insts.push(placed.inst);
+                }
+                abs.add_port(port);
+            }
+
+            let sd_implant = tech.sd_implant(d.mos_type);
             if d.mos_type == MosType::Pmos {
                 let mut psdm_box = rect;
-                expand_box(&mut psdm_box, tc.layer("diff").enclosure("psdm"));
+                expand_box(&mut psdm_box, tc.try_layer("diff")?.enclosure(sd_implant));
 
-                let psdm = layers.keyname("psdm").unwrap();
-                let mut port = AbstractPort::new(format!("psdm_{}", j));
+                let psdm = self.try_get_layerkey(sd_implant)?;
+                let mut port = AbstractPort::new(format!("{}_{}", sd_implant, j));
                 port.add_shape(psdm, Shape::Rect(psdm_box));
                 abs.add_port(port);
 
@@ -120,24 +173,50 @@ impl Pdk {
                 prev_nsdm = None;
 
                 let mut well_box = rect;
-                expand_box(&mut well_box, tc.layer("diff").enclosure("nwell"));
+                expand_box(&mut well_box, tc.try_layer("diff")?.enclosure("nwell"));
+                for tap_rect in tap_rects.iter() {
+                    let mut tap_well = *tap_rect;
+                    expand_box(&mut tap_well, tc.try_layer("ntap")?.enclosure("nwell"));
+                    well_box = well_box.union(&tap_well.into()).into_rect();
+                }
 
                 let mut port = AbstractPort::new(format!("vpb_{}", j));
-                port.add_shape(layers.keyname("nwell").unwrap(), Shape::Rect(well_box));
+                port.add_shape(self.try_get_layerkey("nwell")?, Shape::Rect(well_box));
                 abs.add_port(port);
 
+                let mut keepout = well_box;
+                expand_box(&mut keepout, tc.try_layer("nwell")?.space);
+                dnw_hole = dnw_hole.union(&keepout.into());
+
+                if let Some(prev_nwell) = prev_nwell {
+                    well_box = well_box.union(&prev_nwell.into()).into_rect();
+                }
+
                 elems.push(Element {
                     net: None,
-                    layer: layers.keyname("nwell").unwrap(),
+                    layer: self.try_get_layerkey("nwell")?,
                     purpose: LayerPurpose::Drawing,
                     inner: Shape::Rect(well_box),
                 });
+
+                prev_nwell = Some(well_box);
             } else {
+                prev_nwell = None;
+
+                let mut keepout = rect;
+                expand_box(&mut keepout, tc.space("diff", "nwell"));
+                dnw_hole = dnw_hole.union(&keepout.into());
+                for tap_rect in tap_rects.iter() {
+                    let mut keepout = *tap_rect;
+                    expand_box(&mut keepout, tc.try_layer("ptap")?.enclosure("pwell"));
+                    dnw_hole = dnw_hole.union(&keepout.into());
+                }
+
                 let mut nsdm_box = rect;
-                expand_box(&mut nsdm_box, tc.layer("diff").enclosure("nsdm"));
+                expand_box(&mut nsdm_box, tc.try_layer("diff")?.enclosure(sd_implant));
 
-                let nsdm = layers.keyname("nsdm").unwrap();
-                let mut port = AbstractPort::new(format!("nsdm_{}", j));
+                let nsdm = self.try_get_layerkey(sd_implant)?;
+                let mut port = AbstractPort::new(format!("{}_{}", sd_implant, j));
                 port.add_shape(nsdm, Shape::Rect(nsdm_box));
                 abs.add_port(port);
 
@@ -156,6 +235,30 @@ impl Pdk {
                 });
             }
 
+            if let Some(implant) = implants[j] {
+                let mut vt_box = rect;
+                expand_box(&mut vt_box, tc.try_layer("diff")?.enclosure(implant));
+
+                // Devices sharing an implant are merged into one region;
+                // otherwise, `vt_implant_space` keeps them far enough apart.
+                if j > 0 && implants[j - 1] == Some(implant) {
+                    if let Some(prev_vt) = prev_vt {
+                        vt_box = vt_box.union(&prev_vt.into()).into_rect();
+                    }
+                }
+
+                elems.push(Element {
+                    net: None,
+                    layer: self.try_get_layerkey(implant)?,
+                    purpose: LayerPurpose::Drawing,
+                    inner: Shape::Rect(vt_box),
+                });
+
+                prev_vt = Some(vt_box);
+            } else {
+                prev_vt = None;
+            }
+
             elems.push(Element {
                 net: None,
                 layer: diff,
@@ -168,6 +271,10 @@ impl Pdk {
             prev = Some(d.mos_type);
         }
 
+        if params.dnw {
+            draw_dnw(self, &tc, dnw_hole.into_rect(), &mut elems, &mut abs)?;
+        }
+
         let gate_ctp = ContactParams::builder()
             .rows(1)
             .cols(1)
@@ -175,24 +282,73 @@ impl Pdk {
             .stack("polyc")
             .build()
             .unwrap();
-        let gate_ct = self.get_contact(&gate_ctp);
-        let gate_bbox = gate_ct.bboxes.get(&self.poly()).unwrap();
-        let gate_metal_bbox = gate_ct.bboxes.get(&self.li1()).unwrap();
+        let gate_ct = self.try_get_contact(&gate_ctp)?;
+        let gate_bbox = gate_ct.bboxes.get(&poly).unwrap();
 
         let mut gate_pins = Vec::with_capacity(nf as usize);
+        let mut dummy_pins = Vec::with_capacity(2 * nd as usize);
 
-        let xpoly = x0 - tc.layer("poly").extension("diff");
-        let mut ypoly = y0 + diff_edge_to_gate(&tc);
-        let wpoly = cx - xpoly + tc.layer("poly").extension("diff");
+        // Dummy gates are contacted on the side opposite their nearest real gate.
+        let sides = params.contact_strategy.sides(nf as usize)?;
+        let opposite = |side: GateSide| match side {
+            GateSide::Left => GateSide::Right,
+            GateSide::Right => GateSide::Left,
+        };
+        let mut all_sides = vec![opposite(sides[0]); nd as usize];
+        all_sides.extend(sides.iter().copied());
+        all_sides.extend(vec![opposite(sides[sides.len() - 1]); nd as usize]);
+        let sides = all_sides;
+
+        let sd_contact = |j: usize, d: &MosDevice| {
+            let ct_stack = tech.diff_stack(d.mos_type);
+            self.try_get_contact_sized(ct_stack, Dir::Horiz, diff, d.width)?
+                .ok_or_else(|| {
+                    MosError::BadParams(format!(
+                        "device {} is too narrow to fit a {} contact",
+                        j, ct_stack
+                    ))
+                })
+        };
 
-        // TODO: Need to move gate contacts further away from transistor.
-        // There are several relevant design rules, but for now I'll just
-        // add a constant offset.
-        let poly_fudge_x = 60;
-        let mut poly_rects = Vec::with_capacity(nf as usize);
-        for _ in 0..nf {
+        // Poly is extended further on the sides that carry gate contacts,
+        // so that the contacts stay clear of the diffusion and of the
+        // source/drain contacts of the outermost devices.
+        let poly_ext = |side: GateSide| -> MosResult<Int> {
+            if !sides.contains(&side) {
+                return Ok(tc.try_layer("poly")?.extension("diff"));
+            }
+            let j = match side {
+                GateSide::Left => 0,
+                GateSide::Right => params.devices.len() - 1,
+            };
+            let d = &params.devices[j];
+            let row = Rect::new(
+                Point::new(diff_xs[j], 0),
+                Point::new(diff_xs[j] + d.width, 0),
+            );
+            let li = sd_contact(j, d)?
+                .place_aligned(row, diff, Alignment::new(Align::Center, Align::Start))
+                .bboxes[&sd_metal];
+            let li_overhang = match side {
+                GateSide::Left => row.p0.x - li.p0.x,
+                GateSide::Right => li.p1.x - row.p1.x,
+            };
+            Ok(gate_contact_extension(
+                &tc,
+                &gate_ct,
+                npc,
+                li_overhang.max(0),
+                side,
+            )?)
+        };
+        let xpoly = x0 - poly_ext(GateSide::Left)?;
+        let mut ypoly = y0 + diff_edge_to_gate(&tc)?;
+        let wpoly = cx - xpoly + poly_ext(GateSide::Right)?;
+
+        let mut poly_rects = Vec::with_capacity(nt as usize);
+        for _ in 0..nt {
             let rect = Rect {
-                p0: Point::new(xpoly - poly_fudge_x, ypoly),
+                p0: Point::new(xpoly, ypoly),
                 p1: Point::new(xpoly + wpoly, ypoly + params.length()),
             };
             poly_rects.push(rect);
@@ -204,60 +360,83 @@ impl Pdk {
             });
 
             ypoly += params.length();
-            ypoly += finger_space(&tc);
+            ypoly += finger_space(&tc)?;
         }
 
-        // Place gate contacts and create gate ports
+        // Place gate contacts and create gate ports.
+        //
+        // Consecutive fingers contacted on the same side form a run;
+        // the contacts in each run are spread out evenly around the run's center.
         let line = gate_bbox.height();
-        let space = tc.layer("poly").space;
-        let total_contact_len = nf * line + (nf - 1) * space;
-        let gate_span = Span::new(poly_rects[0].p0.y, poly_rects.last().unwrap().p1.y);
-        let contact_span =
-            Span::from_center_span_gridded(gate_span.center(), total_contact_len, self.grid());
-        let npc_bbox = gate_ct.bboxes.get(&self.npc()).unwrap();
-
-        let mut npc_boxes = Vec::new();
-
-        for i in 0..nf {
-            let bot = contact_span.start() + i * (line + space);
-            let rect = poly_rects[i as usize];
-            let ofsx = rect.p0.x - gate_bbox.p1.x;
-            let ofsy = bot - gate_bbox.p0.y;
-
-            let ct_ofs = Point::new(ofsx, ofsy);
-            let ct_box = translate(gate_metal_bbox, &ct_ofs);
-            let mut port = AbstractPort::new(format!("gate_{}", i));
-            port.add_shape(gate_metal, Shape::Rect(ct_box));
-            abs.add_port(port);
-            gate_pins.push(ct_box);
-
-            npc_boxes.push(translate(npc_bbox, &ct_ofs));
-
-            let inst = Instance {
-                inst_name: format!("gate_contact_{}", i),
-                cell: Ptr::clone(&gate_ct.cell),
-                loc: ct_ofs,
-                reflect_vert: false,
-                angle: None,
-            };
+        let space = tc.try_layer("poly")?.space;
+
+        let mut npc_left = BoundBox::empty();
+        let mut npc_right = BoundBox::empty();
+
+        let mut start = 0;
+        while start < sides.len() {
+            let side = sides[start];
+            let end = (start..sides.len())
+                .find(|&i| sides[i] != side)
+                .unwrap_or(sides.len());
+
+            let n = (end - start) as Int;
+            let total_contact_len = n * line + (n - 1) * space;
+            let gate_span = Span::new(poly_rects[start].p0.y, poly_rects[end - 1].p1.y);
+            let contact_span =
+                Span::from_center_span_gridded(gate_span.center(), total_contact_len, self.grid());
+
+            for (k, i) in (start..end).enumerate() {
+                let bot = contact_span.start() + k as Int * (line + space);
+                let rect = poly_rects[i];
+                // The contact abuts the end of the poly on its side.
+                let (x, horiz) = match side {
+                    GateSide::Left => (rect.p0.x, Align::End),
+                    GateSide::Right => (rect.p1.x, Align::Start),
+                };
+                let edge = Rect::new(Point::new(x, bot), Point::new(x, bot + line));
+                let placed = gate_ct.place_aligned(edge, poly, Alignment::new(horiz, Align::Start));
+
+                let ct_box = placed.bboxes[&gate_metal];
+                let finger = i as Int - nd;
+                let port_name = if finger < 0 || finger >= nf {
+                    dummy_pins.push(ct_box);
+                    format!("dummy_{}", dummy_pins.len() - 1)
+                } else {
+                    gate_pins.push(ct_box);
+                    format!("gate_{}", finger)
+                };
+                let mut port = AbstractPort::new(port_name);
+                port.add_shape(gate_metal, Shape::Rect(ct_box));
+                abs.add_port(port);
+
+                let npc_box = placed.bboxes[&npc];
+                match side {
+                    GateSide::Left => npc_left = npc_left.union(&npc_box.into()),
+                    GateSide::Right => npc_right = npc_right.union(&npc_box.into()),
+                }
 
-            insts.push(inst);
+                insts.push(placed.inst);
+            }
+
+            start = end;
         }
 
-        let top_npc = npc_boxes.last().unwrap();
-        let npc_merge_rect = Rect::new(
-            Point::new(npc_boxes[0].p0.x, npc_boxes[0].p0.y),
-            Point::new(top_npc.p1.x, top_npc.p1.y),
-        );
-        elems.push(Element {
-            net: None,
-            layer: self.npc(),
-            purpose: LayerPurpose::Drawing,
-            inner: Shape::Rect(npc_merge_rect),
-        });
+        for npc_merge in [npc_left, npc_right] {
+            if npc_merge.is_empty() {
+                continue;
+            }
+            elems.push(Element {
+                net: None,
+                layer: npc,
+                purpose: LayerPurpose::Drawing,
+                inner: Shape::Rect(npc_merge.into_rect()),
+            });
+        }
 
-        // Add source/drain contacts
-        let mut cy = y0;
+        // Add source/drain contacts.
+        // The regions on the far side of the dummy gates are left floating.
+        let mut cy = y0 + nd * (params.length() + finger_space(&tc)?);
 
         let mut sd_pins = (0..params.devices.len())
             .map(|_| HashMap::new())
@@ -268,33 +447,20 @@ impl Pdk {
                 if d.skip_sd_metal.contains(&(i as usize)) {
                     continue;
                 }
-                let ct_stack = match d.mos_type {
-                    MosType::Nmos => "ndiffc",
-                    MosType::Pmos => "pdiffc",
-                };
-                let ct = self
-                    .get_contact_sized(ct_stack, Dir::Horiz, diff, d.width)
-                    .unwrap();
-                let bbox = ct.bboxes.get(&diff).unwrap();
-                let ofsx = (d.width - rect_width(bbox)) / 2;
-                let loc = Point::new(x - bbox.p0.x + ofsx, cy - bbox.p0.y);
-                let inst = Instance {
-                    inst_name: format!("sd_contact_{}_{}", i, j),
-                    cell: Ptr::clone(&ct.cell),
-                    loc,
-                    reflect_vert: false,
-                    angle: None,
-                };
-                insts.push(inst);
+                let ct = sd_contact(j, d)?;
+                let row = Rect::new(Point::new(*x, cy), Point::new(x + d.width, cy));
+                let placed =
+                    ct.place_aligned(row, diff, Alignment::new(Align::Center, Align::Start));
+                let sd_rect = placed.bboxes[&sd_metal];
+                insts.push(placed.inst);
 
-                let sd_rect = translate(ct.bboxes.get(&self.li1()).unwrap(), &loc);
                 let mut port = AbstractPort::new(format!("sd_{}_{}", j, i));
                 port.add_shape(sd_metal, Shape::Rect(sd_rect));
                 abs.add_port(port);
                 sd_pins[j].insert(i as Uint, Some(sd_rect));
             }
             cy += params.length();
-            cy += finger_space(&tc);
+            cy += finger_space(&tc)?;
         }
 
         let layout = Layout {
@@ -310,279 +476,276 @@ impl Pdk {
             layout: Some(layout),
         };
 
-        let transistors = LayoutTransistors {
+        let mut transistors = LayoutTransistors {
             cell: Ptr::new(cell),
             sd_metal,
             gate_metal,
             sd_pins,
             gate_pins,
+            dummy_pins,
             num_fingers: params.devices[0].fingers as usize,
             num_devices: params.devices.len(),
+            nets: vec![HashMap::new(); params.devices.len()],
         };
-
-        Ok(Arc::new(transistors))
-    }
-
-    pub(crate) fn draw_contact(&self, params: &ContactParams) -> Ref<Contact> {
-        let rows = params.rows;
-        let cols = params.cols;
-
-        assert!(rows > 0);
-        assert!(cols > 0);
-        let tc = self.config.read().unwrap();
-        let layers = self.layers.read().unwrap();
-        let stack_name = params.stack.clone();
-        let stack = tc.stack(&stack_name);
-        assert_eq!(stack.layers.len(), 3);
-
-        let ctlay_name = &stack.layers[1];
-        let ctlay = layers.keyname(&stack.layers[1]).unwrap();
-
-        let mut elems = Vec::new();
-
-        let x0 = 0;
-        let y0 = 0;
-
-        let ctw = tc.layer(ctlay_name).width;
-        let cts = tc.layer(ctlay_name).space;
-        let ctbw = ctw * cols + cts * (cols - 1);
-        let ctbh = ctw * rows + cts * (rows - 1);
-
-        let ct_bbox = Rect {
-            p0: Point::new(x0, y0),
-            p1: Point::new(x0 + ctbw, y0 + ctbh),
-        };
-
-        let net_name = "x".to_string();
-
-        for i in 0..rows {
-            for j in 0..cols {
-                let left = x0 + j * (ctw + cts);
-                let bot = y0 + i * (ctw + cts);
-                let ct_box = Rect {
-                    p0: Point::new(left, bot),
-                    p1: Point::new(left + ctw, bot + ctw),
-                };
-
-                elems.push(Element {
-                    net: None,
-                    layer: ctlay,
-                    purpose: LayerPurpose::Drawing,
-                    inner: Shape::Rect(ct_box),
-                });
-            }
+        // The layout above is drawn with horizontal gates;
+        // vertical gates are obtained by reflecting across the line `y = x`.
+        if params.direction == Dir::Vert {
+            transistors.transpose()?;
         }
-
-        let mut bboxes = Vec::with_capacity(2);
-        let mut bbox_map = HashMap::with_capacity(3);
-        bbox_map.insert(ctlay, ct_bbox);
-
-        let mut aport = AbstractPort {
-            net: net_name.clone(),
-            shapes: HashMap::new(),
-        };
-
-        for lay_name in [&stack.layers[0], &stack.layers[2]] {
-            let lay = layers.keyname(lay_name).unwrap();
-            let mut laybox = ct_bbox;
-            expand_box(&mut laybox, tc.layer(ctlay_name).enclosure(lay_name));
-            expand_box_min_width(&mut laybox, tc.layer(lay_name).width, tc.grid);
-            let ose = tc.layer(ctlay_name).one_side_enclosure(lay_name);
-
-            match params.dir {
-                Dir::Vert => {
-                    laybox.p0.y = std::cmp::min(laybox.p0.y, ct_bbox.p0.y - ose);
-                    laybox.p1.y = std::cmp::max(laybox.p0.y, ct_bbox.p1.y + ose);
-                }
-                Dir::Horiz => {
-                    laybox.p0.x = std::cmp::min(laybox.p0.x, ct_bbox.p0.x - ose);
-                    laybox.p1.x = std::cmp::max(laybox.p0.x, ct_bbox.p1.x + ose);
-                }
-            }
-
-            let shape = Shape::Rect(laybox);
-            aport.shapes.insert(lay, vec![shape.clone()]);
-
-            bboxes.push(shape.bbox());
-            bbox_map.insert(lay, laybox);
-
-            elems.push(Element {
-                net: None,
-                layer: lay,
-                purpose: LayerPurpose::Drawing,
-                inner: shape,
-            });
+        if let Some(metal) = params.strap {
+            self.strap_transistors(&mut transistors, metal)?;
         }
+        self.connect_sd_nets(&mut transistors, &params)?;
 
-        if params.stack == "ndiffc" || params.stack == "ntap" {
-            let mut nsdm_box = rect_from_bbox(&bboxes[1]);
-            let src_layer = if params.stack == "ndiffc" {
-                "diff"
-            } else {
-                "ntap"
-            };
-            expand_box(&mut nsdm_box, tc.layer(src_layer).enclosure("nsdm"));
-            elems.push(Element {
-                net: None,
-                layer: layers.keyname("nsdm").unwrap(),
-                purpose: LayerPurpose::Drawing,
-                inner: Shape::Rect(nsdm_box),
-            });
-        } else if params.stack == "ptap" {
-            let mut psdm_box = rect_from_bbox(&bboxes[1]);
-            expand_box(&mut psdm_box, tc.layer("ptap").enclosure("psdm"));
-            elems.push(Element {
-                net: None,
-                layer: layers.keyname("psdm").unwrap(),
-                purpose: LayerPurpose::Drawing,
-                inner: Shape::Rect(psdm_box),
-            });
-        } else if params.stack == "pdiffc" {
-            let diff_rect = rect_from_bbox(&bboxes[1]);
-            let mut psdm_box = diff_rect;
-            expand_box(&mut psdm_box, tc.layer("diff").enclosure("psdm"));
-            elems.push(Element {
-                net: None,
-                layer: layers.keyname("psdm").unwrap(),
-                purpose: LayerPurpose::Drawing,
-                inner: Shape::Rect(psdm_box),
-            });
-            let mut well_box = diff_rect;
-            expand_box(&mut well_box, tc.layer("diff").enclosure("nwell"));
-
-            elems.push(Element {
-                net: None,
-                layer: layers.keyname("nwell").unwrap(),
-                purpose: LayerPurpose::Drawing,
-                inner: Shape::Rect(well_box),
-            });
-        } else if params.stack == "polyc" {
-            let mut npc_box = ct_bbox;
-            expand_box(&mut npc_box, tc.layer("licon").enclosure("npc"));
-            bbox_map.insert(layers.keyname("npc").unwrap(), npc_box);
-            elems.push(Element {
-                net: None,
-                layer: layers.keyname("npc").unwrap(),
-                purpose: LayerPurpose::Drawing,
-                inner: Shape::Rect(npc_box),
-            });
-        }
-
-        let bbox = bboxes[0].union(&bboxes[1]);
-        let outline = Rect {
-            p0: bbox.p0,
-            p1: bbox.p1,
-        };
-
-        let name = format!("{}", params);
-
-        let layout = Layout {
-            name: name.clone(),
-            insts: vec![],
-            annotations: vec![],
-            elems,
-        };
-
-        let abs = Abstract {
-            name: name.clone(),
-            outline: Some(Element {
-                net: Some(net_name),
-                layer: layers.keyname(&stack.layers[0]).unwrap(),
-                purpose: LayerPurpose::Drawing,
-                inner: Shape::Rect(outline),
-            }),
-            blockages: HashMap::new(),
-            ports: vec![aport],
-        };
+        Ok(Arc::new(transistors))
+    }
+}
 
-        let cell = Cell {
-            name,
-            abs: Some(abs),
-            layout: Some(layout),
-        };
+/// The SkyWater 130nm process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sky130;
 
-        let cell = Ptr::new(cell);
+impl Technology for Sky130 {
+    fn name(&self) -> &str {
+        "sky130"
+    }
 
-        std::sync::Arc::new(Contact {
-            cell,
-            rows: params.rows,
-            cols: params.cols,
-            bboxes: bbox_map,
-        })
+    fn draw_mos(&self, pdk: &Pdk, params: MosParams) -> MosResult<Ref<LayoutTransistors>> {
+        pdk.draw_sky130_mos(params)
     }
 
-    pub fn metal_name(&self, i: LayerIdx) -> &'static str {
-        match i {
+    fn metal_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        Ok(match i {
             0 => "li",
             1 => "m1",
             2 => "m2",
             3 => "m3",
             4 => "m4",
             5 => "m5",
-            _ => panic!("sky130 has no metal layer numbered {}", i),
-        }
+            _ => return Err(Error::UnknownLayer(format!("metal {}", i))),
+        })
     }
 
-    /// The name of the via layer connecting metal `i+1` to metal `i`.
-    pub fn via_name(&self, i: LayerIdx) -> &'static str {
-        match i {
+    fn via_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        Ok(match i {
             0 => "mcon",
             1 => "via",
             2 => "via2",
             3 => "via3",
             4 => "via4",
-            _ => panic!("sky130 has no via layer numbered {}", i),
-        }
+            _ => return Err(Error::UnknownLayer(format!("via {}", i))),
+        })
     }
 
-    /// The name of the stack connecting metal `i+1` to metal `i`.
-    pub fn stack_name(&self, layer: LayerIdx) -> &str {
-        match layer {
+    fn stack_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        Ok(match i {
             0 => "viali",
             1 => "via1",
             2 => "via2",
             3 => "via3",
             4 => "via4",
-            _ => panic!("No stack for layer index {}", layer),
+            _ => return Err(Error::UnknownStack(format!("above metal {}", i))),
+        })
+    }
+
+    fn diff_stack(&self, mos_type: MosType) -> &'static str {
+        match mos_type {
+            MosType::Nmos => "ndiffc",
+            MosType::Pmos => "pdiffc",
         }
     }
 
-    pub fn metal(&self, i: LayerIdx) -> LayerKey {
-        self.get_layerkey(self.metal_name(i)).unwrap()
+    fn tap_stack(&self, mos_type: MosType) -> &'static str {
+        match mos_type {
+            MosType::Nmos => "ptap",
+            MosType::Pmos => "ntap",
+        }
     }
 
-    pub fn via(&self, i: LayerIdx) -> LayerKey {
-        self.get_layerkey(self.via_name(i)).unwrap()
+    fn sd_implant(&self, mos_type: MosType) -> &'static str {
+        match mos_type {
+            MosType::Nmos => "nsdm",
+            MosType::Pmos => "psdm",
+        }
     }
+
+    fn vt_implant(&self, mos_type: MosType, intent: &Intent) -> MosResult<Option<&'static str>> {
+        match (intent, mos_type) {
+            (Intent::Svt, _) => Ok(None),
+            (Intent::Lvt, _) => Ok(Some("lvtn")),
+            (Intent::Hvt, MosType::Pmos) => Ok(Some("hvtp")),
+            (intent, mos_type) => Err(MosError::BadParams(format!(
+                "sky130 does not support {} devices with intent {}",
+                mos_type, intent
+            ))),
+        }
+    }
+}
+
+pub fn finger_space(tc: &TechConfig) -> PdkResult<Int> {
+    Ok([
+        2 * tc.space("gate", "licon") + tc.try_layer("li")?.width,
+        tc.try_layer("poly")?.space,
+    ]
+    .into_iter()
+    .max()
+    .unwrap())
 }
 
-pub fn finger_space(tc: &TechConfig) -> Int {
-    [
-        2 * tc.space("gate", "licon") + tc.layer("li").width,
-        tc.layer("poly").space,
+pub fn diff_edge_to_gate(tc: &TechConfig) -> PdkResult<Int> {
+    Ok([
+        tc.try_layer("diff")?.extension("poly"),
+        tc.space("gate", "licon")
+            + tc.try_layer("licon")?.width
+            + tc.try_layer("licon")?.enclosure("diff"),
     ]
     .into_iter()
     .max()
-    .unwrap()
+    .unwrap())
 }
 
-pub fn diff_edge_to_gate(tc: &TechConfig) -> Int {
-    [
-        tc.layer("diff").extension("poly"),
-        tc.space("gate", "licon") + tc.layer("licon").width + tc.layer("licon").enclosure("diff"),
+/// Returns how far poly must extend past the diffusion to reach
+/// a gate contact `ct` placed on the given `side`.
+///
+/// The contact's poly pad abuts the end of the poly, so the offset is the
+/// largest of the poly extension past diff, the gate to npc spacing,
+/// the licon to diff spacing and the li spacing, each measured to the
+/// relevant edge of the contact. `li_overhang` is how far the source/drain
+/// li reaches past the diffusion edge on that side.
+pub(crate) fn gate_contact_extension(
+    tc: &TechConfig,
+    ct: &Contact,
+    npc: LayerKey,
+    li_overhang: Int,
+    side: GateSide,
+) -> PdkResult<Int> {
+    let li = ct.bboxes[&ct.top];
+    let poly = ct.bboxes[&ct.bot];
+    let licon = ct.bboxes[&ct.cut];
+    let npc = ct.bboxes[&npc];
+
+    // Distances from the diff-facing edge of the poly pad to the
+    // diff-facing edges of the npc, licon and li shapes.
+    let (npc_overhang, licon_inset, li_inset) = match side {
+        GateSide::Left => (
+            npc.p1.x - poly.p1.x,
+            poly.p1.x - licon.p1.x,
+            poly.p1.x - li.p1.x,
+        ),
+        GateSide::Right => (
+            poly.p0.x - npc.p0.x,
+            licon.p0.x - poly.p0.x,
+            li.p0.x - poly.p0.x,
+        ),
+    };
+
+    let ext = [
+        tc.try_layer("poly")?.extension("diff"),
+        tc.space("gate", "npc") + npc_overhang,
+        tc.space("licon", "diff") - licon_inset,
+        tc.try_layer("li")?.space + li_overhang - li_inset,
     ]
     .into_iter()
     .max()
-    .unwrap()
+    .unwrap();
+
+    Ok((ext + tc.grid - 1) / tc.grid * tc.grid)
 }
 
-pub fn diff_to_opposite_diff(tc: &TechConfig) -> Int {
-    tc.space("diff", "nwell") + tc.layer("diff").enclosure("nwell")
+pub fn diff_to_opposite_diff(tc: &TechConfig) -> PdkResult<Int> {
+    Ok(tc.space("diff", "nwell") + tc.try_layer("diff")?.enclosure("nwell"))
 }
 
-/// Calculates the width of the given rectangle.
+/// Draws a deep n-well and its n-well ring around the given `hole`.
 ///
-/// Assumes that `r.p1.x > r.p0.x`.
-fn rect_width(r: &Rect) -> Int {
-    r.p1.x - r.p0.x
+/// The `hole` is the region enclosed by the inner edge of the n-well ring.
+/// Adds abstract ports named `nwell_ring` and `dnwell`.
+fn draw_dnw(
+    pdk: &Pdk,
+    tc: &TechConfig,
+    hole: Rect,
+    elems: &mut Vec<Element>,
+    abs: &mut Abstract,
+) -> PdkResult<()> {
+    let nwell = pdk.try_get_layerkey("nwell")?;
+    let dnwell = pdk.try_get_layerkey("dnwell")?;
+
+    let mut dnw_box = hole;
+    expand_box(&mut dnw_box, tc.try_layer("dnwell")?.extension("nwell"));
+    expand_box_min_width(&mut dnw_box, tc.try_layer("dnwell")?.width, tc.grid);
+
+    let mut outer = dnw_box;
+    expand_box(&mut outer, tc.try_layer("dnwell")?.enclosure("nwell"));
+
+    let ring = [
+        Rect::new(outer.p0, Point::new(outer.p1.x, hole.p0.y)),
+        Rect::new(Point::new(outer.p0.x, hole.p1.y), outer.p1),
+        Rect::new(
+            Point::new(outer.p0.x, hole.p0.y),
+            Point::new(hole.p0.x, hole.p1.y),
+        ),
+        Rect::new(
+            Point::new(hole.p1.x, hole.p0.y),
+            Point::new(outer.p1.x, hole.p1.y),
+        ),
+    ];
+
+    let mut port = AbstractPort::new("nwell_ring");
+    for rect in ring {
+        port.add_shape(nwell, Shape::Rect(rect));
+        elems.push(Element {
+            net: None,
+            layer: nwell,
+            purpose: LayerPurpose::Drawing,
+            inner: Shape::Rect(rect),
+        });
+    }
+    abs.add_port(port);
+
+    let mut port = AbstractPort::new("dnwell");
+    port.add_shape(dnwell, Shape::Rect(dnw_box));
+    abs.add_port(port);
+
+    elems.push(Element {
+        net: None,
+        layer: dnwell,
+        purpose: LayerPurpose::Drawing,
+        inner: Shape::Rect(dnw_box),
+    });
+
+    Ok(())
+}
+
+/// The minimum spacing between opposite-type diffusion regions
+/// when both have tap strips.
+///
+/// Keeps each tap's implant clear of the neighboring device's implant.
+pub fn tapped_diff_to_opposite_diff(tc: &TechConfig) -> PdkResult<Int> {
+    Ok([
+        tc.try_layer("diff")?.enclosure("psdm")
+            + tc.try_layer("psdm")?.space
+            + tc.try_layer("ptap")?.enclosure("psdm"),
+        tc.try_layer("diff")?.enclosure("nsdm")
+            + tc.try_layer("nsdm")?.space
+            + tc.try_layer("ntap")?.enclosure("nsdm"),
+        diff_to_opposite_diff(tc)?,
+    ]
+    .into_iter()
+    .max()
+    .unwrap())
+}
+
+/// The minimum spacing between adjacent diffusion regions
+/// with threshold voltage implants `a` and `b`.
+///
+/// Devices that share an implant layer are placed in a single merged implant
+/// region, so there is no additional constraint between them.
+pub fn vt_implant_space(tc: &TechConfig, a: Option<&str>, b: Option<&str>) -> PdkResult<Int> {
+    let diff = tc.try_layer("diff")?;
+    Ok(match (a, b) {
+        (Some(a), Some(b)) if a == b => 0,
+        (Some(a), Some(b)) => diff.enclosure(a) + tc.space(a, b) + diff.enclosure(b),
+        (Some(x), None) | (None, Some(x)) => diff.enclosure(x) + tc.space(x, "diff"),
+        (None, None) => 0,
+    })
 }
@@ -1,16 +1,26 @@
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use layout21::raw::geom::Dir;
-use layout21::raw::{Point, Rect};
+use layout21::raw::{BoundBoxTrait, Element, Layout, Point, Rect, Shape};
 use layout21::{
-    raw::{DepOrder, LayerPurpose, Library},
+    raw::{Cell, DepOrder, LayerPurpose, Library},
     utils::{Ptr, PtrList},
 };
 
+use super::Sky130;
+use crate::config::{ContactStack, StackImplant};
+use crate::drc::{assert_drc_clean, assert_no_violations, DrcRule};
+use crate::geometry::{rect_from_bbox, translate, transpose};
+use crate::tech::Technology;
 use crate::{
-    contact::ContactParams,
-    mos::{MosDevice, MosParams, MosType},
+    contact::{Align, Alignment, Contact, ContactParams, EnclosureOverride, Side},
+    mos::{
+        FingerParity, GateContactStrategy, Intent, LayoutTransistors, MosDevice, MosError,
+        MosParams, MosResult, MosType, SdNets,
+    },
 };
+use crate::{Error, LayerIdx, Pdk, PdkResult, Ref};
 
 #[test]
 fn test_draw_sky130_mos_nand2() -> Result<(), Box<dyn std::error::Error>> {
@@ -39,6 +49,7 @@ fn test_draw_sky130_mos_nand2() -> Result<(), Box<dyn std::error::Error>> {
     let pdk = super::pdk()?;
 
     let cell = pdk.draw_sky130_mos(params)?;
+    assert_drc_clean(&pdk, &cell.cell);
 
     let mut lib = Library::new(
         "test_draw_sky130_mos_nand2",
@@ -54,6 +65,791 @@ fn test_draw_sky130_mos_nand2() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_draw_sky130_mos_vt_implants() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let mut params = MosParams::new();
+    params
+        .dnw(false)
+        .direction(Dir::Horiz)
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(150)
+                .fingers(2)
+                .intent(Intent::Lvt)
+                .build()
+                .unwrap(),
+        )
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(150)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        )
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Pmos)
+                .width(1_400)
+                .length(150)
+                .fingers(2)
+                .intent(Intent::Hvt)
+                .build()
+                .unwrap(),
+        )
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Pmos)
+                .width(1_400)
+                .length(150)
+                .fingers(2)
+                .intent(Intent::Lvt)
+                .build()
+                .unwrap(),
+        );
+
+    let pdk = super::pdk()?;
+    let ptx = pdk.draw_sky130_mos(params)?;
+
+    let lvtn = pdk.get_layerkey("lvtn").unwrap();
+    let hvtp = pdk.get_layerkey("hvtp").unwrap();
+    {
+        let cell = ptx.cell.read().unwrap();
+        let elems = &cell.layout.as_ref().unwrap().elems;
+        assert_eq!(elems.iter().filter(|e| e.layer == lvtn).count(), 2);
+        assert_eq!(elems.iter().filter(|e| e.layer == hvtp).count(), 1);
+    }
+
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    let mut lib = Library::new(
+        "test_draw_sky130_mos_vt_implants",
+        pdk.config.read().unwrap().units,
+    );
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&ptx.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_sky130_mos_vt_implants.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_unsupported_intent() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for (mos_type, intent) in [
+        (MosType::Nmos, Intent::Ulvt),
+        (MosType::Nmos, Intent::Hvt),
+        (MosType::Pmos, Intent::Uhvt),
+        (MosType::Pmos, Intent::Custom("esd".to_string())),
+    ] {
+        let mut params = MosParams::new();
+        params.add_device(
+            MosDevice::builder()
+                .mos_type(mos_type)
+                .width(1_000)
+                .length(150)
+                .intent(intent)
+                .build()
+                .unwrap(),
+        );
+        assert!(matches!(
+            pdk.draw_sky130_mos(params),
+            Err(MosError::BadParams(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_dnw() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let mut params = MosParams::new();
+    params.dnw(true).direction(Dir::Horiz).add_device(
+        MosDevice::builder()
+            .mos_type(MosType::Nmos)
+            .width(1_000)
+            .length(150)
+            .fingers(4)
+            .build()
+            .unwrap(),
+    );
+
+    let pdk = super::pdk()?;
+    let ptx = pdk.draw_sky130_mos(params)?;
+
+    let nwell = pdk.get_layerkey("nwell").unwrap();
+    let dnwell = pdk.get_layerkey("dnwell").unwrap();
+
+    let ring = ptx.nwell_ring_port().unwrap();
+    assert_eq!(ring.shapes.get(&nwell).unwrap().len(), 4);
+    let dnw = ptx.dnwell_port().unwrap().largest_rect(dnwell)?;
+
+    // Single-side gate contacts are spread out over more than the span of their
+    // fingers, so their poly pads come within poly spacing of the neighbouring
+    // fingers. This is independent of the deep n-well.
+    let violations = pdk
+        .drc(&ptx.cell)
+        .into_iter()
+        .filter(|v| !(v.layer == "poly" && v.rule == DrcRule::Space))
+        .collect::<Vec<_>>();
+    assert_no_violations(&violations);
+
+    let tc = pdk.config();
+    let tc = tc.read().unwrap();
+    assert!(dnw.width() >= tc.layer("dnwell").width);
+    assert!(dnw.height() >= tc.layer("dnwell").width);
+
+    let mut lib = Library::new("test_draw_sky130_mos_dnw", tc.units);
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&ptx.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_sky130_mos_dnw.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_gate_contact_strategies() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let mut lib = Library::new(
+        "test_draw_sky130_mos_gate_contact_strategies",
+        pdk.config.read().unwrap().units,
+    );
+    lib.layers = pdk.layers();
+
+    for (strategy, left) in [
+        (
+            GateContactStrategy::Alternate,
+            [true, false, true, false, true, false],
+        ),
+        (
+            GateContactStrategy::Abba,
+            [true, false, false, true, true, false],
+        ),
+    ] {
+        let mut params = MosParams::new();
+        params
+            .direction(Dir::Horiz)
+            .contact_strategy(strategy)
+            .add_device(
+                MosDevice::builder()
+                    .mos_type(MosType::Nmos)
+                    .width(1_000)
+                    .length(150)
+                    .fingers(6)
+                    .build()
+                    .unwrap(),
+            );
+
+        let ptx = pdk.draw_sky130_mos(params)?;
+        assert_eq!(ptx.gate_pins.len(), 6);
+        for (pin, left) in ptx.gate_pins.iter().zip(left) {
+            if left {
+                assert!(pin.p1.x < 0);
+            } else {
+                assert!(pin.p0.x > 1_000);
+            }
+        }
+        lib.cells.push(Ptr::clone(&ptx.cell));
+    }
+
+    let mut params = MosParams::new();
+    params
+        .contact_strategy(GateContactStrategy::Other("spiral".to_string()))
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(150)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        );
+    assert!(matches!(
+        pdk.draw_sky130_mos(params),
+        Err(MosError::BadParams(_))
+    ));
+
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_sky130_mos_gate_contact_strategies.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_vertical() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let mut params = MosParams::new();
+    params
+        .direction(Dir::Horiz)
+        .contact_strategy(GateContactStrategy::Alternate)
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(150)
+                .fingers(3)
+                .skip_sd_metal(vec![1])
+                .build()
+                .unwrap(),
+        )
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Pmos)
+                .width(1_400)
+                .length(150)
+                .fingers(3)
+                .build()
+                .unwrap(),
+        );
+
+    let horiz = pdk.draw_sky130_mos(params.clone())?;
+    params.direction(Dir::Vert);
+    let vert = pdk.draw_sky130_mos(params)?;
+
+    for (h, v) in horiz.gate_pins.iter().zip(vert.gate_pins.iter()) {
+        assert_eq!(transpose(h), *v);
+    }
+    for i in 0..2 {
+        for j in 0..=3 {
+            assert_eq!(horiz.sd_pin(i, j).map(|r| transpose(&r)), vert.sd_pin(i, j));
+        }
+    }
+    let li = pdk.get_layerkey("li").unwrap();
+    assert_eq!(
+        transpose(&horiz.gate_port(2).unwrap().largest_rect(li)?),
+        vert.gate_port(2).unwrap().largest_rect(li)?
+    );
+
+    assert_drc_clean(&pdk, &horiz.cell);
+    assert_drc_clean(&pdk, &vert.cell);
+
+    let mut lib = Library::new(
+        "test_draw_sky130_mos_vertical",
+        pdk.config.read().unwrap().units,
+    );
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&horiz.cell));
+    lib.cells.push(Ptr::clone(&vert.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_sky130_mos_vertical.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_mos_fixed_gate_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let mut tc = super::tech_config();
+    tc.gate_dir = Some(Dir::Horiz);
+    let pdk = Pdk::new(arcstr::literal!("sky130"), tc)?;
+
+    let mut params = MosParams::new();
+    params.direction(Dir::Vert).add_device(
+        MosDevice::builder()
+            .mos_type(MosType::Nmos)
+            .width(1_000)
+            .length(150)
+            .build()
+            .unwrap(),
+    );
+
+    assert!(matches!(
+        pdk.draw_sky130_mos(params.clone()),
+        Err(MosError::UnsupportedDirection(Dir::Vert))
+    ));
+    params.direction(Dir::Horiz);
+    pdk.draw_sky130_mos(params)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_taps() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let mut params = MosParams::new();
+    params
+        .direction(Dir::Horiz)
+        .taps(true)
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(150)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        )
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Pmos)
+                .width(1_400)
+                .length(150)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        );
+
+    let pdk = super::pdk()?;
+    let ptx = pdk.draw_sky130_mos(params)?;
+
+    let li = pdk.get_layerkey("li").unwrap();
+    let nwell = pdk.get_layerkey("nwell").unwrap();
+
+    let vnb = ptx.vnb_tap_port(0).unwrap();
+    assert_eq!(vnb.shapes.get(&li).unwrap().len(), 2);
+    assert!(ptx.vpb_tap_port(0).is_none());
+
+    let vpb = ptx.vpb_tap_port(1).unwrap();
+    assert_eq!(vpb.shapes.get(&li).unwrap().len(), 2);
+    assert!(ptx.vnb_tap_port(1).is_none());
+
+    let well = ptx.vpb_port(1).unwrap().largest_rect(nwell)?;
+    for shape in vpb.shapes.get(&li).unwrap() {
+        let bbox = shape.bbox();
+        assert!(well.p0.x < bbox.p0.x && well.p0.y < bbox.p0.y);
+        assert!(well.p1.x > bbox.p1.x && well.p1.y > bbox.p1.y);
+    }
+
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    let mut lib = Library::new(
+        "test_draw_sky130_mos_taps",
+        pdk.config.read().unwrap().units,
+    );
+    lib.layers = pdk.layers();
+    lib.cells.push(Ptr::clone(&ptx.cell));
+    let cells = DepOrder::order(&lib);
+    lib.cells = PtrList::from_ptrs(cells);
+    let gds = lib.to_gds()?;
+    gds.save(output("test_draw_sky130_mos_taps.gds"))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_strap() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for metal in [1, 2] {
+        for direction in [Dir::Horiz, Dir::Vert] {
+            let mut params = MosParams::new();
+            params
+                .direction(direction)
+                .contact_strategy(GateContactStrategy::Alternate)
+                .strap(metal)
+                .add_device(
+                    MosDevice::builder()
+                        .mos_type(MosType::Nmos)
+                        .width(1_000)
+                        .length(150)
+                        .fingers(3)
+                        .skip_sd_metal(vec![1])
+                        .build()
+                        .unwrap(),
+                )
+                .add_device(
+                    MosDevice::builder()
+                        .mos_type(MosType::Pmos)
+                        .width(1_400)
+                        .length(150)
+                        .fingers(3)
+                        .build()
+                        .unwrap(),
+                );
+            let ptx = pdk.draw_sky130_mos(params)?;
+
+            let strap = pdk.metal(metal);
+            assert_eq!(ptx.sd_metal, strap);
+            assert_eq!(ptx.gate_metal, strap);
+            assert!(ptx.sd_pin(0, 1).is_none());
+            let pin = ptx.sd_pin(1, 2).unwrap();
+            assert_eq!(ptx.sd_port(1, 2).unwrap().largest_rect(strap).unwrap(), pin);
+            let pin = ptx.gate_pin(2).unwrap();
+            assert_eq!(ptx.gate_port(2).unwrap().largest_rect(strap).unwrap(), pin);
+            assert_drc_clean(&pdk, &ptx.cell);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_strap_dense() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let params = |metal, mos_type| {
+        let mut params = MosParams::new();
+        params
+            .contact_strategy(GateContactStrategy::Alternate)
+            .strap(metal);
+        for width in [360, 420] {
+            params.add_device(
+                MosDevice::builder()
+                    .mos_type(mos_type)
+                    .width(width)
+                    .length(150)
+                    .fingers(6)
+                    .build()
+                    .unwrap(),
+            );
+        }
+        params
+    };
+
+    for mos_type in [MosType::Nmos, MosType::Pmos] {
+        for metal in [1, 2] {
+            let ptx = pdk.draw_sky130_mos(params(metal, mos_type))?;
+            assert_drc_clean(&pdk, &ptx.cell);
+        }
+
+        // Metal 3 pads are too wide to fit between neighbouring fingers.
+        assert!(matches!(
+            pdk.draw_sky130_mos(params(3, mos_type)),
+            Err(MosError::BadParams(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_sd_nets() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for strap in [None, Some(1)] {
+        for direction in [Dir::Horiz, Dir::Vert] {
+            let mut params = MosParams::new();
+            params
+                .direction(direction)
+                .contact_strategy(GateContactStrategy::Alternate)
+                .add_device(
+                    MosDevice::builder()
+                        .mos_type(MosType::Nmos)
+                        .width(1_000)
+                        .length(150)
+                        .fingers(4)
+                        .build()
+                        .unwrap(),
+                )
+                .add_device(
+                    MosDevice::builder()
+                        .mos_type(MosType::Pmos)
+                        .width(1_400)
+                        .length(150)
+                        .fingers(4)
+                        .build()
+                        .unwrap(),
+                )
+                .sd_nets(
+                    0,
+                    SdNets::Alternating {
+                        even: "vss".to_string(),
+                        odd: "y".to_string(),
+                    },
+                )
+                .sd_nets(
+                    1,
+                    SdNets::PerRegion(
+                        ["vdd", "y", "vdd", "y", "x"]
+                            .into_iter()
+                            .map(String::from)
+                            .collect(),
+                    ),
+                );
+            params.strap = strap;
+            let ptx = pdk.draw_sky130_mos(params)?;
+
+            assert_eq!(ptx.net_pins(0, "vss").len(), 3);
+            assert_eq!(ptx.net_pins(1, "vdd").len(), 2);
+            assert_eq!(ptx.net_pins(0, "y").len(), 2);
+            assert_eq!(ptx.net_pins(1, "y").len(), 2);
+            assert_eq!(ptx.net_pins(1, "x").len(), 1);
+            assert!(ptx.net_pins(0, "vdd").is_empty());
+            assert!(ptx.net_pins(0, "a").is_empty());
+            assert!(ptx.sd_port(0, 0).is_none());
+            assert_eq!(ptx.sd_pin(0, 0), Some(ptx.net_pins(0, "vss")[0]));
+
+            // Straps are drawn on the metal above the source/drain pins,
+            // and only short regions within the same device.
+            let strap_metal = pdk.metal(strap.unwrap_or(0) + 1);
+            for j in 0..2 {
+                let y = ptx.net_port(j, "y").unwrap();
+                assert_eq!(y.shapes[&ptx.sd_metal].len(), 2);
+                assert_eq!(y.shapes[&strap_metal].len(), 1);
+            }
+            assert!(ptx.net_port(0, "vdd").is_none());
+            assert!(!ptx
+                .net_port(1, "x")
+                .unwrap()
+                .shapes
+                .contains_key(&strap_metal));
+            assert_drc_clean(&pdk, &ptx.cell);
+        }
+    }
+
+    let mut params = MosParams::new();
+    params
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Nmos)
+                .width(1_000)
+                .length(150)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        )
+        .sd_nets(0, SdNets::PerRegion(vec!["a".to_string(), "b".to_string()]));
+    assert!(matches!(
+        pdk.draw_sky130_mos(params.clone()),
+        Err(MosError::BadParams(_))
+    ));
+    params.sd_nets.clear();
+    params.sd_nets(
+        1,
+        SdNets::Alternating {
+            even: "a".to_string(),
+            odd: "b".to_string(),
+        },
+    );
+    assert!(matches!(
+        pdk.draw_sky130_mos(params),
+        Err(MosError::BadParams(_))
+    ));
+
+    // Net ports must not shadow the other ports of the device.
+    let mut params = MosParams::new();
+    params
+        .add_device(
+            MosDevice::builder()
+                .mos_type(MosType::Pmos)
+                .width(1_000)
+                .length(150)
+                .fingers(2)
+                .build()
+                .unwrap(),
+        )
+        .sd_nets(
+            0,
+            SdNets::Alternating {
+                even: "vpb".to_string(),
+                odd: "y".to_string(),
+            },
+        );
+    assert!(matches!(
+        pdk.draw_sky130_mos(params),
+        Err(MosError::BadParams(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_dummies() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let poly = pdk.get_layerkey("poly").unwrap();
+
+    for (direction, strap) in [(Dir::Horiz, None), (Dir::Vert, None), (Dir::Horiz, Some(1))] {
+        let mut params = MosParams::new();
+        params
+            .direction(direction)
+            .contact_strategy(GateContactStrategy::Alternate)
+            .dummies(1)
+            .taps(true)
+            .add_device(
+                MosDevice::builder()
+                    .mos_type(MosType::Nmos)
+                    .width(1_000)
+                    .length(150)
+                    .fingers(2)
+                    .build()
+                    .unwrap(),
+            );
+        params.strap = strap;
+        let ptx = pdk.draw_sky130_mos(params)?;
+
+        assert_eq!(ptx.gate_pins.len(), 2);
+        assert_eq!(ptx.dummy_pins.len(), 2);
+        assert!(ptx.gate_port(1).is_some());
+        assert!(ptx.gate_port(2).is_none());
+        let metal = pdk.metal(strap.unwrap_or(0));
+        for k in 0..2 {
+            let port = ptx.dummy_port(k).unwrap();
+            assert_eq!(
+                port.largest_rect(metal).unwrap(),
+                ptx.dummy_pins[k as usize]
+            );
+        }
+        assert!(ptx.dummy_port(2).is_none());
+        assert_eq!(ptx.sd_pins[0].len(), 3);
+        {
+            let cell = ptx.cell.read().unwrap();
+            let elems = &cell.layout.as_ref().unwrap().elems;
+            assert_eq!(elems.iter().filter(|e| e.layer == poly).count(), 4);
+        }
+        assert_drc_clean(&pdk, &ptx.cell);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_folded() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let device = |mos_type, total_width| {
+        let device = MosDevice::builder()
+            .mos_type(mos_type)
+            .width(1_000)
+            .length(150)
+            .build()
+            .unwrap();
+        (device, total_width)
+    };
+    let devices = vec![device(MosType::Nmos, 3_000), device(MosType::Pmos, 4_510)];
+
+    let params = MosParams::folded(&pdk, devices.clone(), 1_000, FingerParity::Any)?;
+    assert_eq!(params.fingers(), 5);
+    assert_eq!(params.devices[0].width, 600);
+    assert_eq!(params.devices[1].width, 900);
+    assert_eq!(params.devices[1].total_width(), 4_500);
+
+    let params = MosParams::folded(&pdk, devices.clone(), 1_000, FingerParity::Odd)?;
+    assert_eq!(params.fingers(), 5);
+
+    let mut params = MosParams::folded(&pdk, devices.clone(), 1_000, FingerParity::Even)?;
+    assert_eq!(params.fingers(), 6);
+    assert_eq!(params.devices[0].width, 500);
+    // 4510 / 6 is about 751.7, which rounds to the nearest grid point.
+    assert_eq!(params.devices[1].width, 750);
+    for d in params.devices.iter() {
+        assert_eq!(d.width % pdk.grid(), 0);
+    }
+
+    params.contact_strategy(GateContactStrategy::Alternate);
+    let ptx = pdk.draw_sky130_mos(params)?;
+    assert_eq!(ptx.num_fingers, 6);
+    assert_drc_clean(&pdk, &ptx.cell);
+
+    assert!(matches!(
+        MosParams::folded(&pdk, devices, 0, FingerParity::Any),
+        Err(MosError::BadParams(_))
+    ));
+
+    // The narrow device would be split into fingers below the minimum diff width
+    // to match the wide device's finger count.
+    let min_width = pdk.config.read().unwrap().layer("diff").width;
+    let devices = vec![
+        device(MosType::Nmos, 3_000),
+        device(MosType::Pmos, 3 * min_width - 3 * pdk.grid()),
+    ];
+    assert!(matches!(
+        MosParams::folded(&pdk, devices, 1_000, FingerParity::Any),
+        Err(MosError::BadParams(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_draw_sky130_mos_gate_contact_offset() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let tc = pdk.config.read().unwrap().clone();
+
+    let ctp = ContactParams::builder()
+        .rows(1)
+        .cols(1)
+        .dir(Dir::Horiz)
+        .stack("polyc")
+        .build()
+        .unwrap();
+    let ct = pdk.get_contact(&ctp);
+    let li = pdk.get_layerkey("li").unwrap();
+    let licon = pdk.get_layerkey("licon").unwrap();
+    let poly = pdk.get_layerkey("poly").unwrap();
+    let npc = pdk.get_layerkey("npc").unwrap();
+    let diff = pdk.get_layerkey("diff").unwrap();
+
+    for length in [150, 180, 500] {
+        for width in [420, 1_000, 2_000] {
+            let mut params = MosParams::new();
+            params
+                .dnw(false)
+                .direction(Dir::Horiz)
+                .contact_strategy(GateContactStrategy::Alternate)
+                .add_device(
+                    MosDevice::builder()
+                        .mos_type(MosType::Nmos)
+                        .width(width)
+                        .length(length)
+                        .fingers(2)
+                        .build()
+                        .unwrap(),
+                );
+            let ptx = pdk.draw_sky130_mos(params)?;
+
+            let (diff_rect, poly_rects, npc_rects) = {
+                let cell = ptx.cell.read().unwrap();
+                let elems = &cell.layout.as_ref().unwrap().elems;
+                let rects = |layer| {
+                    elems
+                        .iter()
+                        .filter(|e| e.layer == layer)
+                        .map(|e| e.inner.bbox().into_rect())
+                        .collect::<Vec<_>>()
+                };
+                (rects(diff)[0], rects(poly), rects(npc))
+            };
+
+            for rect in poly_rects.iter() {
+                assert!(diff_rect.p0.x - rect.p0.x >= tc.layer("poly").extension("diff"));
+                assert!(rect.p1.x - diff_rect.p1.x >= tc.layer("poly").extension("diff"));
+            }
+            for rect in npc_rects.iter() {
+                let gap = (diff_rect.p0.x - rect.p1.x).max(rect.p0.x - diff_rect.p1.x);
+                assert!(gap >= tc.space("gate", "npc"), "L={} W={}", length, width);
+            }
+
+            // Gate 0 is contacted on the left and gate 1 on the right.
+            for (i, pin) in ptx.gate_pins.iter().enumerate() {
+                let offset = Point::new(pin.p0.x - ct.bboxes[&li].p0.x, 0);
+                let cut = translate(&ct.bboxes[&licon], &offset);
+                let gap = if i == 0 {
+                    diff_rect.p0.x - cut.p1.x
+                } else {
+                    cut.p0.x - diff_rect.p1.x
+                };
+                assert!(gap >= tc.space("licon", "diff"), "L={} W={}", length, width);
+
+                for sd in ptx.sd_pins[0].values().flatten() {
+                    let gap = (sd.p0.x - pin.p1.x).max(pin.p0.x - sd.p1.x);
+                    assert!(gap >= tc.layer("li").space, "L={} W={}", length, width);
+                }
+            }
+
+            assert_drc_clean(&pdk, &ptx.cell);
+        }
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_sky130_draw_contact() -> Result<(), Box<dyn std::error::Error>> {
     setup()?;
@@ -66,7 +862,9 @@ fn test_sky130_draw_contact() -> Result<(), Box<dyn std::error::Error>> {
 
     for i in 1..=n {
         for j in 1..=n {
-            for stack in ["ntap", "ndiffc", "pdiffc", "polyc", "viali", "via1", "via2"] {
+            for stack in [
+                "ntap", "ndiffc", "pdiffc", "polyc", "viali", "via1", "via2", "via3", "via4",
+            ] {
                 for dir in [Dir::Horiz, Dir::Vert] {
                     let mut cp = ContactParams::builder();
                     let cp = cp
@@ -88,6 +886,559 @@ fn test_sky130_draw_contact() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_sky130_config_contact_stacks() -> Result<(), Box<dyn std::error::Error>> {
+    let mut tc = super::tech_config();
+    tc.add_stack(
+        "lvt_ndiffc",
+        ContactStack {
+            layers: vec!["li".into(), "licon".into(), "diff".into()],
+            implants: vec![
+                StackImplant {
+                    layer: "nsdm".into(),
+                    around: "diff".into(),
+                    enclosure: None,
+                },
+                StackImplant {
+                    layer: "lvtn".into(),
+                    around: "diff".into(),
+                    enclosure: Some(200),
+                },
+            ],
+        },
+    );
+    tc.add_stack(
+        "broken",
+        ContactStack {
+            layers: vec!["licon".into()],
+            implants: vec![],
+        },
+    );
+    let pdk = Pdk::new(arcstr::literal!("sky130"), tc)?;
+
+    let ct = pdk.get_contact(
+        &ContactParams::builder()
+            .stack("lvt_ndiffc")
+            .rows(1)
+            .cols(2)
+            .dir(Dir::Vert)
+            .build()?,
+    );
+    let diff = ct.bboxes[&pdk.get_layerkey("diff").unwrap()];
+    let nsdm = ct.bboxes[&pdk.get_layerkey("nsdm").unwrap()];
+    let lvtn = ct.bboxes[&pdk.get_layerkey("lvtn").unwrap()];
+    assert_eq!(nsdm.p0.x, diff.p0.x - 125);
+    assert_eq!(lvtn.p0.x, diff.p0.x - 200);
+    assert_eq!(lvtn.p1.y, diff.p1.y + 200);
+
+    let sized = pdk
+        .get_contact_sized(
+            "lvt_ndiffc",
+            Dir::Horiz,
+            pdk.get_layerkey("lvtn").unwrap(),
+            1_000,
+        )
+        .unwrap();
+    let lvtn = sized.bboxes[&pdk.get_layerkey("lvtn").unwrap()];
+    assert!(lvtn.width() <= 1_000);
+
+    assert!(matches!(
+        pdk.try_get_contact(
+            &ContactParams::builder()
+                .stack("broken")
+                .rows(1)
+                .cols(1)
+                .dir(Dir::Vert)
+                .build()?
+        ),
+        Err(Error::InvalidStack(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_sky130_contact_ports() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let ct = pdk.get_contact(
+        &ContactParams::builder()
+            .stack("polyc")
+            .rows(2)
+            .cols(3)
+            .dir(Dir::Horiz)
+            .build()?,
+    );
+
+    let li = pdk.get_layerkey("li").unwrap();
+    let poly = pdk.get_layerkey("poly").unwrap();
+    let licon = pdk.get_layerkey("licon").unwrap();
+    let npc = pdk.get_layerkey("npc").unwrap();
+    assert_eq!(ct.cut_count(), 6);
+    assert_eq!(ct.top_rect(), ct.bboxes[&li]);
+    assert_eq!(ct.bot_rect(), ct.bboxes[&poly]);
+    assert_eq!(ct.cut_rect(), ct.bboxes[&licon]);
+
+    let cell = ct.cell.read().unwrap();
+    let abs = cell.abs.as_ref().unwrap();
+    let mut nets = abs.ports.iter().map(|p| p.net.as_str()).collect::<Vec<_>>();
+    nets.sort_unstable();
+    assert_eq!(nets, ["li", "poly"]);
+    for port in abs.ports.iter() {
+        let layer = pdk.get_layerkey(&port.net).unwrap();
+        assert_eq!(port.shapes[&layer], vec![Shape::Rect(ct.bboxes[&layer])]);
+    }
+
+    let outline = match abs.outline.as_ref().unwrap().inner {
+        Shape::Rect(r) => r,
+        _ => panic!("expected a rectangular outline"),
+    };
+    let conducting = [li, poly, licon]
+        .iter()
+        .map(|l| ct.bboxes[l])
+        .reduce(|a, b| rect_from_bbox(&a.union(&b.bbox())))
+        .unwrap();
+    assert_eq!(outline, conducting);
+    // Implants do not block routing, so they are left out of the outline.
+    assert_ne!(
+        rect_from_bbox(&outline.union(&ct.bboxes[&npc].bbox())),
+        outline
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sky130_contact_enclosure_overrides() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let m1 = pdk.get_layerkey("m1").unwrap();
+    let li = pdk.get_layerkey("li").unwrap();
+
+    let params = |enclosures: Vec<EnclosureOverride>| {
+        ContactParams::builder()
+            .stack("viali")
+            .rows(1)
+            .cols(2)
+            .dir(Dir::Horiz)
+            .enclosures(enclosures)
+            .build()
+            .unwrap()
+    };
+
+    let plain = pdk.get_contact(&params(vec![]));
+    let ct = pdk.get_contact(&params(vec![EnclosureOverride {
+        layer: "m1".into(),
+        one_side: Some(vec![Side::Top, Side::Bottom]),
+        min_size: None,
+    }]));
+    let cut = ct.cut_rect();
+    let m1_box = ct.bboxes[&m1];
+    assert_eq!(cut.p0.x - m1_box.p0.x, 30);
+    assert_eq!(m1_box.p1.x - cut.p1.x, 30);
+    assert_eq!(cut.p0.y - m1_box.p0.y, 60);
+    assert_eq!(m1_box.p1.y - cut.p1.y, 60);
+    assert_eq!(ct.bboxes[&li], plain.bboxes[&li]);
+    assert_ne!(
+        ct.cell.read().unwrap().name,
+        plain.cell.read().unwrap().name
+    );
+
+    let ct = pdk.get_contact(&params(vec![EnclosureOverride {
+        layer: "li".into(),
+        one_side: None,
+        min_size: Some((700, 400)),
+    }]));
+    let li_box = ct.bboxes[&li];
+    assert_eq!((li_box.width(), li_box.height()), (700, 400));
+    assert_eq!(li_box.p0.x + li_box.p1.x, cut.p0.x + cut.p1.x);
+
+    let res = pdk.try_get_contact(&params(vec![EnclosureOverride {
+        layer: "m1".into(),
+        one_side: Some(vec![Side::Left]),
+        min_size: None,
+    }]));
+    assert!(matches!(res, Err(Error::InvalidEnclosure(layer)) if layer == "m1"));
+
+    let res = pdk.try_get_contact(&params(vec![EnclosureOverride {
+        layer: "m2".into(),
+        one_side: None,
+        min_size: Some((500, 500)),
+    }]));
+    assert!(matches!(res, Err(Error::UnknownLayer(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_sky130_metal_stacks() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let mut lib = Library::new("test_sky130_metal_stacks", pdk.config.read().unwrap().units);
+    lib.layers = pdk.layers();
+
+    for i in 0..=4 {
+        let cp = ContactParams::builder()
+            .stack(pdk.stack_name(i).to_string())
+            .rows(2)
+            .cols(2)
+            .dir(Dir::Vert)
+            .build()
+            .unwrap();
+        let ct = pdk.get_contact(&cp);
+
+        let cell = ct.cell.read().unwrap();
+        let elems = &cell.layout.as_ref().unwrap().elems;
+        for layer in [pdk.metal(i), pdk.via(i), pdk.metal(i + 1)] {
+            assert!(elems.iter().any(|e| e.layer == layer));
+        }
+        assert!(ct.bboxes.contains_key(&pdk.metal(i)));
+        assert!(ct.bboxes.contains_key(&pdk.metal(i + 1)));
+
+        lib.cells.push(Ptr::clone(&ct.cell));
+    }
+
+    let gds = lib.to_gds()?;
+    gds.save(output("test_sky130_metal_stacks.gds"))?;
+    Ok(())
+}
+
+#[test]
+fn test_sky130_via_stack() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    let mut lib = Library::new("test_sky130_via_stack", pdk.config.read().unwrap().units);
+    lib.layers = pdk.layers();
+
+    let within = Rect::new(Point::new(-500, 200), Point::new(700, 1400));
+    let vs = pdk.via_stack(0, 3, within).expect("via stack should fit");
+
+    assert_eq!(vs.contacts.len(), 3);
+    for i in 0..=3 {
+        let bbox = vs.bboxes[&pdk.metal(i)];
+        assert!(bbox.p0.x >= 0 && bbox.p0.y >= 0);
+        assert!(bbox.p1.x <= within.width() && bbox.p1.y <= within.height());
+    }
+    for i in 0..3 {
+        assert!(vs.bboxes.contains_key(&pdk.via(i)));
+    }
+    assert_drc_clean(&pdk, &vs.cell);
+
+    let cached = pdk.via_stack(0, 3, Rect::new(Point::zero(), Point::new(1200, 1200)));
+    assert!(Arc::ptr_eq(&vs, &cached.unwrap()));
+
+    assert!(pdk
+        .via_stack(0, 3, Rect::new(Point::zero(), Point::new(100, 100)))
+        .is_none());
+    assert!(matches!(
+        pdk.try_via_stack(2, 1, within),
+        Err(Error::InvalidViaStack { from: 2, to: 1 })
+    ));
+
+    lib.cells.push(Ptr::clone(&vs.cell));
+    lib.cells = PtrList::from_ptrs(DepOrder::order(&lib));
+    let gds = lib.to_gds()?;
+    gds.save(output("test_sky130_via_stack.gds"))?;
+    Ok(())
+}
+
+#[test]
+fn test_sky130_connect_rects() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let pdk = super::pdk()?;
+
+    // Wide crossing wires fit a contact array entirely within the overlap.
+    let m1 = Rect::new(Point::new(0, 0), Point::new(2_000, 800));
+    let m2 = Rect::new(Point::new(600, -500), Point::new(1_400, 1_500));
+    let conn = pdk.connect_rects(m1, 1, m2, 2);
+    let overlap = Rect::new(Point::new(600, 0), Point::new(1_400, 800));
+    for pad in [conn.bot_pad, conn.top_pad] {
+        assert!(pad.p0.x >= overlap.p0.x && pad.p0.y >= overlap.p0.y);
+        assert!(pad.p1.x <= overlap.p1.x && pad.p1.y <= overlap.p1.y);
+    }
+    assert!(conn.contact.rows * conn.contact.cols > 1);
+
+    // The largest contacts fitting either metal alone overflow the other metal,
+    // but a smaller contact fits both.
+    let m2 = Rect::new(Point::new(0, 0), Point::new(700, 5_000));
+    let m3 = Rect::new(Point::new(-2_000, 0), Point::new(5_000, 740));
+    let overlap = Rect::new(Point::new(0, 0), Point::new(700, 740));
+    let (bot, top) = (pdk.metal(2), pdk.metal(3));
+    for layer in [bot, top] {
+        let ct = pdk
+            .get_contact_within(pdk.stack_name(2), layer, overlap)
+            .unwrap();
+        assert!([bot, top].iter().any(|l| {
+            let r = ct.bboxes[l];
+            r.width() > overlap.width() || r.height() > overlap.height()
+        }));
+    }
+    let conn = pdk.connect_rects(m2, 2, m3, 3);
+    for pad in [conn.bot_pad, conn.top_pad] {
+        assert!(pad.p0.x >= overlap.p0.x && pad.p0.y >= overlap.p0.y);
+        assert!(pad.p1.x <= overlap.p1.x && pad.p1.y <= overlap.p1.y);
+    }
+
+    // Minimum width wires fall back to a single cut centered on the overlap.
+    let li = Rect::new(Point::new(0, -1_000), Point::new(170, 1_000));
+    let m1 = Rect::new(Point::new(-1_000, 0), Point::new(1_000, 140));
+    let conn = pdk.connect_rects(m1, 1, li, 0);
+    assert_eq!((conn.contact.rows, conn.contact.cols), (1, 1));
+    let cut = translate(&conn.contact.bboxes[&pdk.via(0)], &conn.inst.loc);
+    assert!((cut.p0.x + cut.p1.x - 170).abs() <= 10);
+    assert!((cut.p0.y + cut.p1.y - 140).abs() <= 10);
+
+    let mut elems = Vec::new();
+    for (rect, layer) in [(li, 0), (m1, 1)] {
+        elems.push(Element {
+            net: None,
+            layer: pdk.metal(layer),
+            purpose: LayerPurpose::Drawing,
+            inner: Shape::Rect(rect),
+        });
+    }
+    let name = "test_sky130_connect_rects".to_string();
+    let cell = Cell {
+        name: name.clone(),
+        abs: None,
+        layout: Some(Layout {
+            name,
+            insts: vec![conn.inst],
+            annotations: vec![],
+            elems,
+        }),
+    };
+    assert_drc_clean(&pdk, &Ptr::new(cell));
+
+    assert!(matches!(
+        pdk.try_connect_rects(li, 0, m1, 2),
+        Err(Error::InvalidViaStack { .. })
+    ));
+    let far = Rect::new(Point::new(5_000, 5_000), Point::new(6_000, 6_000));
+    assert!(matches!(
+        pdk.try_connect_rects(li, 0, far, 1),
+        Err(Error::NoOverlap)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_sky130_pdk_lib_registers_cells() -> Result<(), Box<dyn std::error::Error>> {
+    setup()?;
+    let mut lib = super::pdk_lib("test_sky130_pdk_lib_registers_cells")?;
+
+    let cp = ContactParams::builder()
+        .stack("viali")
+        .rows(1)
+        .cols(2)
+        .dir(Dir::Vert)
+        .build()?;
+    let ct1 = lib.draw_contact(&cp);
+    let ct2 = lib.draw_contact(&cp);
+    assert!(Arc::ptr_eq(&ct1, &ct2));
+    assert_eq!(lib.lib.cells.len(), 1);
+
+    let mut params = MosParams::new();
+    params.dnw(false).direction(Dir::Horiz).add_device(
+        MosDevice::builder()
+            .mos_type(MosType::Nmos)
+            .width(1_000)
+            .length(150)
+            .fingers(2)
+            .build()
+            .unwrap(),
+    );
+    let ptx = lib.draw_mos(params.clone())?;
+    lib.draw_mos(params)?;
+
+    // The transistor cell and the contacts it instances are each registered once.
+    let mut names = lib
+        .lib
+        .cells
+        .iter()
+        .map(|cell| cell.read().unwrap().name.clone())
+        .collect::<Vec<_>>();
+    let count = names.len();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), count);
+    assert!(lib.lib.cells.iter().any(|cell| *cell == ptx.cell));
+    let insts = ptx
+        .cell
+        .read()
+        .unwrap()
+        .layout
+        .as_ref()
+        .unwrap()
+        .insts
+        .clone();
+    for inst in insts.iter() {
+        assert!(lib.lib.cells.iter().any(|cell| *cell == inst.cell));
+    }
+
+    lib.save_gds(output("test_sky130_pdk_lib_registers_cells.gds"))?;
+    Ok(())
+}
+
+#[test]
+fn test_sky130_fallible_api() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    assert!(matches!(
+        pdk.try_get_layerkey("m9"),
+        Err(Error::UnknownLayer(_))
+    ));
+    assert!(matches!(pdk.try_metal(6), Err(Error::UnknownLayer(_))));
+    assert!(matches!(pdk.try_via(5), Err(Error::UnknownLayer(_))));
+    assert!(matches!(pdk.try_stack_name(5), Err(Error::UnknownStack(_))));
+    {
+        let tc = pdk.config.read().unwrap();
+        assert!(matches!(tc.try_layer("m9"), Err(Error::UnknownLayer(_))));
+        assert!(matches!(tc.try_stack("via9"), Err(Error::UnknownStack(_))));
+    }
+
+    let mut cp = ContactParams::builder();
+    cp.stack("via9").rows(1).cols(1).dir(Dir::Vert);
+    assert!(matches!(
+        pdk.try_get_contact(&cp.build()?),
+        Err(Error::UnknownStack(_))
+    ));
+    cp.stack("via1").rows(0);
+    assert!(matches!(
+        pdk.try_get_contact(&cp.build()?),
+        Err(Error::InvalidContactSize { rows: 0, cols: 1 })
+    ));
+    assert!(matches!(
+        pdk.try_get_contact_sized("viali", Dir::Horiz, pdk.metal(3), 1_000),
+        Err(Error::UnknownLayer(_))
+    ));
+
+    let mut lib = Pdk::new(arcstr::literal!("unknown"), super::tech_config())?
+        .create_pdk_lib("test_sky130_fallible_api");
+    let mut params = MosParams::new();
+    params.add_device(
+        MosDevice::builder()
+            .mos_type(MosType::Nmos)
+            .width(1_000)
+            .length(150)
+            .build()
+            .unwrap(),
+    );
+    assert!(matches!(
+        lib.draw_mos(params.clone()),
+        Err(MosError::Pdk(Error::UnsupportedTech(_)))
+    ));
+
+    // Drawing with a config that lacks a layer the generator needs is an error.
+    let mut yaml = serde_yaml::to_value(super::tech_config())?;
+    yaml["layers"]
+        .as_mapping_mut()
+        .unwrap()
+        .remove(&serde_yaml::Value::from("dnwell"));
+    let pdk = Pdk::new(arcstr::literal!("sky130"), serde_yaml::from_value(yaml)?)?;
+    params.dnw(true);
+    assert!(matches!(
+        pdk.draw_sky130_mos(params),
+        Err(MosError::Pdk(Error::UnknownLayer(_)))
+    ));
+
+    Ok(())
+}
+
+/// A technology that reuses the sky130 generators,
+/// but only exposes two metal layers and has no low threshold voltage devices.
+#[derive(Debug)]
+struct TwoMetal;
+
+impl Technology for TwoMetal {
+    fn name(&self) -> &str {
+        "two_metal"
+    }
+
+    fn draw_mos(&self, pdk: &Pdk, params: MosParams) -> MosResult<Ref<LayoutTransistors>> {
+        Sky130.draw_mos(pdk, params)
+    }
+
+    fn draw_contact(&self, pdk: &Pdk, params: &ContactParams) -> PdkResult<Ref<Contact>> {
+        Sky130.draw_contact(pdk, params)
+    }
+
+    fn metal_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        match i {
+            0 | 1 => Sky130.metal_name(i),
+            _ => Err(Error::UnknownLayer(format!("metal {}", i))),
+        }
+    }
+
+    fn via_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        match i {
+            0 => Sky130.via_name(i),
+            _ => Err(Error::UnknownLayer(format!("via {}", i))),
+        }
+    }
+
+    fn stack_name(&self, i: LayerIdx) -> PdkResult<&'static str> {
+        match i {
+            0 => Sky130.stack_name(i),
+            _ => Err(Error::UnknownStack(format!("above metal {}", i))),
+        }
+    }
+
+    fn diff_stack(&self, mos_type: MosType) -> &'static str {
+        Sky130.diff_stack(mos_type)
+    }
+
+    fn tap_stack(&self, mos_type: MosType) -> &'static str {
+        Sky130.tap_stack(mos_type)
+    }
+
+    fn sd_implant(&self, mos_type: MosType) -> &'static str {
+        Sky130.sd_implant(mos_type)
+    }
+
+    fn vt_implant(&self, mos_type: MosType, intent: &Intent) -> MosResult<Option<&'static str>> {
+        match intent {
+            Intent::Lvt => Err(MosError::BadParams(
+                "two_metal has no low threshold voltage devices".to_string(),
+            )),
+            intent => Sky130.vt_implant(mos_type, intent),
+        }
+    }
+}
+
+#[test]
+fn test_custom_technology() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = Pdk::with_technology(Arc::new(TwoMetal), super::tech_config())?;
+    assert_eq!(&pdk.tech, "two_metal");
+    assert_eq!(pdk.technology()?.name(), "two_metal");
+
+    assert_eq!(pdk.metal(1), pdk.get_layerkey("m1").unwrap());
+    assert!(matches!(pdk.try_metal(2), Err(Error::UnknownLayer(_))));
+    assert_eq!(pdk.stack_name(0), "viali");
+    assert!(pdk.try_stack_name(1).is_err());
+
+    let mut lib = pdk.create_pdk_lib("test_custom_technology");
+    let mut params = MosParams::new();
+    params.add_device(
+        MosDevice::builder()
+            .mos_type(MosType::Nmos)
+            .width(1_000)
+            .length(150)
+            .fingers(2)
+            .build()
+            .unwrap(),
+    );
+    lib.draw_mos(params.clone())?;
+
+    // The generators look up process details through the custom technology.
+    params.devices[0].intent = Intent::Lvt;
+    assert!(super::pdk()?.draw_sky130_mos(params.clone()).is_ok());
+    assert!(matches!(lib.draw_mos(params), Err(MosError::BadParams(_))));
+
+    Ok(())
+}
+
 #[test]
 fn test_sky130_contact_sized() -> Result<(), Box<dyn std::error::Error>> {
     setup()?;
@@ -109,6 +1460,40 @@ fn test_sky130_contact_sized() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_sky130_contact_sizing_is_maximal() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let m1 = pdk.metal(1);
+
+    for width in (300..3_000).step_by(85) {
+        let ct = match pdk.get_contact_sized("viali", Dir::Horiz, m1, width) {
+            Some(ct) => ct,
+            None => continue,
+        };
+        assert!(ct.bboxes[&m1].width() <= width);
+
+        let bigger = pdk.get_contact(
+            &ContactParams::builder()
+                .stack("viali")
+                .rows(1)
+                .cols(ct.cols + 1)
+                .dir(Dir::Horiz)
+                .build()?,
+        );
+        assert!(bigger.bboxes[&m1].width() > width);
+    }
+
+    // Only the contact that is returned gets drawn and cached.
+    let pdk = super::pdk()?;
+    let li = pdk.metal(0);
+    let bbox = Rect::new(Point::zero(), Point::new(2_000, 700));
+    let ct = pdk.get_contact_within("viali", li, bbox).unwrap();
+    assert!(ct.bboxes[&li].width() <= 2_000 && ct.bboxes[&li].height() <= 700);
+    assert_eq!(pdk.contacts.read().unwrap().len(), 1);
+
+    Ok(())
+}
+
 #[test]
 fn test_sky130_draw_contact_within() -> Result<(), Box<dyn std::error::Error>> {
     setup()?;
@@ -161,6 +1546,89 @@ fn test_serialize_layer_purpose() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn test_sky130_contact_placement() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+    let ct = pdk.get_contact(
+        &ContactParams::builder()
+            .stack("viali")
+            .rows(1)
+            .cols(3)
+            .dir(Dir::Horiz)
+            .build()
+            .unwrap(),
+    );
+    let m1 = pdk.metal(1);
+
+    let a = ct.place_centered(Point::new(1_000, 2_000), m1);
+    let r = a.bboxes[&m1];
+    assert!((r.p0.x + r.p1.x - 2_000).abs() <= 2 * pdk.grid());
+    assert!((r.p0.y + r.p1.y - 4_000).abs() <= 2 * pdk.grid());
+    for (layer, bbox) in ct.bboxes.iter() {
+        assert_eq!(a.bboxes[layer], translate(bbox, &a.inst.loc));
+    }
+    assert_eq!(a.top_rect(), a.bboxes[&ct.top]);
+
+    let within = Rect::new(Point::new(0, 0), Point::new(5_000, 5_000));
+    let b = ct.place_aligned(within, m1, Alignment::new(Align::Start, Align::End));
+    assert_eq!(b.bboxes[&m1].p0.x, 0);
+    assert_eq!(b.bboxes[&m1].p1.y, 5_000);
+    assert_ne!(a.inst.inst_name, b.inst.inst_name);
+
+    Ok(())
+}
+
+#[test]
+fn test_sky130_contact_array_spacing() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    let m1 = pdk.metal(1);
+    let within = Rect::new(Point::zero(), Point::new(3_000, 3_000));
+    let ct = pdk.get_contact_within("via1", m1, within).unwrap();
+    assert!(ct.rows >= 4 && ct.cols >= 4);
+    let bbox = ct.bboxes[&m1];
+    assert!(bbox.width() <= within.width() && bbox.height() <= within.height());
+    assert_drc_clean(&pdk, &ct.cell);
+
+    // Adding another row or column would not fit once the array spacing applies.
+    let tc = pdk.config.read().unwrap();
+    let via = tc.layer("via");
+    let pitch = via.width + via.cut_space(ct.rows, ct.cols);
+    assert!(bbox.width() + pitch > within.width());
+
+    Ok(())
+}
+
+#[test]
+fn test_sky130_contact_min_area() -> Result<(), Box<dyn std::error::Error>> {
+    let pdk = super::pdk()?;
+
+    for i in 0..5 {
+        let ct = pdk.get_contact(
+            &ContactParams::builder()
+                .stack(pdk.stack_name(i).to_string())
+                .rows(1)
+                .cols(1)
+                .dir(Dir::Vert)
+                .build()
+                .unwrap(),
+        );
+        for metal in [pdk.metal(i), pdk.metal(i + 1)] {
+            let area = pdk
+                .config
+                .read()
+                .unwrap()
+                .layer(&pdk.layer_name(metal))
+                .area;
+            let bbox = ct.bboxes[&metal];
+            assert!(bbox.width() * bbox.height() >= area);
+        }
+        assert_drc_clean(&pdk, &ct.cell);
+    }
+
+    Ok(())
+}
+
 fn output(name: impl AsRef<Path>) -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("target/_tests/gds/")
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use layout21::raw::geom::Dir;
+use layout21::raw::{
+    Abstract, AbstractPort, BoundBoxTrait, Cell, Element, Instance, LayerKey, LayerPurpose, Layout,
+    Point, Rect, Shape,
+};
+use layout21::utils::Ptr;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Int, Uint};
+use crate::contact::{Alignment, Contact, ContactParams, MAX_CONTACT_UNITS};
+use crate::geometry::rect_from_bbox;
+use crate::{Error, LayerIdx, Pdk, PdkResult, Ref};
+
+/// Parameters of a stack of vias connecting metal `from` up to metal `to`.
+///
+/// Every level of the stack fits within a `width` by `height` rectangle
+/// whose lower left corner is at the origin.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ViaStackParams {
+    pub from: LayerIdx,
+    pub to: LayerIdx,
+    pub width: Int,
+    pub height: Int,
+}
+
+/// A cell containing one contact for every level between two metals.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ViaStack {
+    pub cell: Ptr<Cell>,
+    /// The contacts making up the stack, from bottom to top.
+    pub contacts: Vec<Ref<Contact>>,
+    /// The bounding box of every layer in the stack, in the cell's coordinates.
+    pub bboxes: HashMap<LayerKey, Rect>,
+}
+
+/// A contact placed at the overlap of two rectangles on adjacent metals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViaConnection {
+    pub inst: Instance,
+    pub contact: Ref<Contact>,
+    /// The placed contact's rect on the lower metal.
+    pub bot_pad: Rect,
+    /// The placed contact's rect on the upper metal.
+    pub top_pad: Rect,
+}
+
+impl Pdk {
+    /// Gets a stack of vias connecting metal `from` to metal `to`, with every
+    /// layer of the stack fitting within the provided [`Rect`]'s width and height.
+    ///
+    /// The stack is drawn relative to the lower left corner of `within`,
+    /// so it should be placed at `within.p0`.
+    ///
+    /// Returns [`None`] if some level of the stack does not fit.
+    pub fn via_stack(&self, from: LayerIdx, to: LayerIdx, within: Rect) -> Option<Ref<ViaStack>> {
+        self.try_via_stack(from, to, within)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`Pdk::via_stack`], but returns an error if the metals cannot be connected.
+    pub fn try_via_stack(
+        &self,
+        from: LayerIdx,
+        to: LayerIdx,
+        within: Rect,
+    ) -> PdkResult<Option<Ref<ViaStack>>> {
+        let params = ViaStackParams {
+            from,
+            to,
+            width: within.width(),
+            height: within.height(),
+        };
+        if let Some(vs) = self.via_stacks.read().unwrap().get(&params) {
+            return Ok(Some(vs.clone()));
+        }
+        let vs = match self.draw_via_stack(&params)? {
+            Some(vs) => vs,
+            None => return Ok(None),
+        };
+        self.via_stacks.write().unwrap().insert(params, vs.clone());
+        Ok(Some(vs))
+    }
+
+    fn draw_via_stack(&self, params: &ViaStackParams) -> PdkResult<Option<Ref<ViaStack>>> {
+        if params.from >= params.to {
+            return Err(Error::InvalidViaStack {
+                from: params.from,
+                to: params.to,
+            });
+        }
+
+        let within = Rect::new(Point::zero(), Point::new(params.width, params.height));
+        let name = format!(
+            "via_stack_m{}_m{}_{}x{}",
+            params.from, params.to, params.width, params.height
+        );
+
+        let mut contacts = Vec::new();
+        let mut insts = Vec::new();
+        let mut bboxes: HashMap<LayerKey, Rect> = HashMap::new();
+
+        for i in params.from..params.to {
+            let stack = self.try_stack_name(i)?;
+            let bot = self.try_metal(i)?;
+            let top = self.try_metal(i + 1)?;
+            let ct = match self.fit_via(stack, bot, top, &within)? {
+                Some(ct) => ct,
+                None => return Ok(None),
+            };
+
+            // Center this level's cuts, and with them its metals, within the landing rect.
+            let placed = ct.place_aligned(within, ct.cut, Alignment::CENTER);
+            for (layer, bbox) in placed.bboxes.iter() {
+                bboxes
+                    .entry(*layer)
+                    .and_modify(|r| *r = union(r, bbox))
+                    .or_insert(*bbox);
+            }
+
+            insts.push(placed.inst);
+            contacts.push(ct);
+        }
+
+        // Adjacent levels may land differently shaped rects on the metal
+        // between them, so fill each metal out to its bounding box.
+        let net_name = "x".to_string();
+        let mut elems = Vec::new();
+        let mut aport = AbstractPort {
+            net: net_name.clone(),
+            shapes: HashMap::new(),
+        };
+        let mut outline = None;
+        for i in params.from..=params.to {
+            let metal = self.try_metal(i)?;
+            let bbox = bboxes[&metal];
+            let shape = Shape::Rect(bbox);
+            elems.push(Element {
+                net: None,
+                layer: metal,
+                purpose: LayerPurpose::Drawing,
+                inner: shape.clone(),
+            });
+            if i == params.from || i == params.to {
+                aport.shapes.insert(metal, vec![shape]);
+            }
+            outline = Some(outline.map_or(bbox, |r| union(&r, &bbox)));
+        }
+
+        let layout = Layout {
+            name: name.clone(),
+            insts,
+            annotations: vec![],
+            elems,
+        };
+
+        let abs = Abstract {
+            name: name.clone(),
+            outline: Some(Element {
+                net: Some(net_name),
+                layer: self.try_metal(params.to)?,
+                purpose: LayerPurpose::Drawing,
+                inner: Shape::Rect(outline.unwrap()),
+            }),
+            blockages: HashMap::new(),
+            ports: vec![aport],
+        };
+
+        let cell = Ptr::new(Cell {
+            name,
+            abs: Some(abs),
+            layout: Some(layout),
+        });
+
+        Ok(Some(Ref::new(ViaStack {
+            cell,
+            contacts,
+            bboxes,
+        })))
+    }
+
+    /// Connects `a` on metal `a_layer` to `b` on metal `b_layer` with the
+    /// largest contact whose landing pads fit within the overlap of the two rects.
+    ///
+    /// If no contact fits, a single cut is centered on the overlap, and its
+    /// landing pads may extend beyond it.
+    pub fn connect_rects(
+        &self,
+        a: Rect,
+        a_layer: LayerIdx,
+        b: Rect,
+        b_layer: LayerIdx,
+    ) -> ViaConnection {
+        self.try_connect_rects(a, a_layer, b, b_layer)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like [`Pdk::connect_rects`], but returns an error if the metals are not
+    /// adjacent or the rects do not overlap.
+    pub fn try_connect_rects(
+        &self,
+        a: Rect,
+        a_layer: LayerIdx,
+        b: Rect,
+        b_layer: LayerIdx,
+    ) -> PdkResult<ViaConnection> {
+        let (bot, top) = (a_layer.min(b_layer), a_layer.max(b_layer));
+        if top != bot + 1 {
+            return Err(Error::InvalidViaStack {
+                from: a_layer,
+                to: b_layer,
+            });
+        }
+        let overlap = a.intersection(&b.bbox());
+        if overlap.is_empty() {
+            return Err(Error::NoOverlap);
+        }
+        let overlap = rect_from_bbox(&overlap);
+
+        let stack = self.try_stack_name(bot)?;
+        let bot = self.try_metal(bot)?;
+        let top = self.try_metal(top)?;
+        let dir = if overlap.width() > overlap.height() {
+            Dir::Horiz
+        } else {
+            Dir::Vert
+        };
+        let ct = match self.fit_via(stack, bot, top, &overlap)? {
+            Some(ct) => ct,
+            None => self.try_get_contact(
+                &ContactParams::builder()
+                    .stack(stack)
+                    .rows(1)
+                    .cols(1)
+                    .dir(dir)
+                    .build()
+                    .unwrap(),
+            )?,
+        };
+
+        let placed = ct.place_aligned(overlap, ct.cut, Alignment::CENTER);
+        Ok(ViaConnection {
+            bot_pad: placed.bboxes[&bot],
+            top_pad: placed.bboxes[&top],
+            inst: placed.inst,
+            contact: ct,
+        })
+    }
+
+    /// Gets the contact on `stack` with the most cuts whose `bot` and `top` rects
+    /// both fit within `within`.
+    fn fit_via(
+        &self,
+        stack: &str,
+        bot: LayerKey,
+        top: LayerKey,
+        within: &Rect,
+    ) -> PdkResult<Option<Ref<Contact>>> {
+        let dir = if within.width() > within.height() {
+            Dir::Horiz
+        } else {
+            Dir::Vert
+        };
+        // The most cuts along `axis`, with `other` cuts across it,
+        // for which both landing pads are at most `limit` long.
+        let max_units = |axis: Dir, other: Uint, limit: Int| -> PdkResult<Option<Uint>> {
+            let mut n = MAX_CONTACT_UNITS;
+            for layer in [bot, top] {
+                match self.max_contact_units(stack, layer, dir, axis, other, limit)? {
+                    Some(m) => n = n.min(m),
+                    None => return Ok(None),
+                }
+            }
+            Ok(Some(n))
+        };
+
+        // Every array that fits both pads has at most `max_rows` rows; for each number
+        // of rows, the widest array is the only one worth trying.
+        let max_rows = match max_units(Dir::Vert, 1, within.height())? {
+            Some(rows) => rows,
+            None => return Ok(None),
+        };
+        let mut candidates = Vec::new();
+        for rows in 1..=max_rows {
+            if let Some(cols) = max_units(Dir::Horiz, rows, within.width())? {
+                candidates.push((rows, cols));
+            }
+        }
+        candidates.sort_by_key(|&(rows, cols)| std::cmp::Reverse(rows * cols));
+
+        for (rows, cols) in candidates {
+            let ct = self.try_get_contact(
+                &ContactParams::builder()
+                    .stack(stack)
+                    .rows(rows)
+                    .cols(cols)
+                    .dir(dir)
+                    .build()
+                    .unwrap(),
+            )?;
+            let fits = [bot, top].iter().all(|l| {
+                let r = &ct.bboxes[l];
+                r.width() <= within.width() && r.height() <= within.height()
+            });
+            if fits {
+                return Ok(Some(ct));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn union(a: &Rect, b: &Rect) -> Rect {
+    rect_from_bbox(&a.union(&b.bbox()))
+}